@@ -0,0 +1,758 @@
+use crossterm::event::{KeyCode, KeyModifiers};
+use std::mem;
+
+use crate::{
+    app::{App, InputMode, Priority, View},
+    keymap::{key_name, Keymap},
+};
+
+// everything a key press can do. the event loop only turns keys into these, `App::apply`
+// does the rest, so tests can drive the app without a terminal
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Action {
+    // the Normal mode actions that can be rebound in keys.toml
+    MoveUp,
+    MoveDown,
+    Add,
+    Delete,
+    Edit,
+    Search,
+    Quit,
+    // the rest of Normal mode
+    ClearMarks,
+    Restore,
+    // `g`, twice jumps to the top
+    G,
+    // one digit of the count before a motion
+    Digit(usize),
+    // `G`, the last todo or the one the count says
+    SelectLast,
+    PageUp,
+    PageDown,
+    NextIncomplete,
+    PreviousIncomplete,
+    // the marked todos if there are any, otherwise the selected one
+    ToggleComplete,
+    ToggleAll,
+    ToggleMark,
+    MarkAllShown,
+    ShowDetails,
+    ShowHelp,
+    // 1/2/3
+    SetPriority(Priority),
+    RaisePriority,
+    LowerPriority,
+    CycleSort,
+    FilterByTag,
+    ExportMarkdown,
+    CycleStatusFilter,
+    CycleAgeFilter,
+    ToggleGroupByDate,
+    ClearSearch,
+    MoveTodoUp,
+    MoveTodoDown,
+    AddSubtask,
+    EditNotes,
+    Collapse,
+    Expand,
+    Undo,
+    Redo,
+    Copy,
+    ToggleTheme,
+    TogglePreview,
+    ToggleView,
+    ArchiveCompleted,
+    ClearAll,
+    EmptyTrash,
+    NextList,
+    PreviousList,
+    // 0 based
+    SwitchList(usize),
+    StartCommand,
+    // searching
+    // typing and moving the cursor in the search box
+    SearchInput(KeyCode),
+    ToggleCaseSensitive,
+    ToggleSearchMode,
+    ToggleWholeWord,
+    // the text inputs
+    Input(KeyCode, KeyModifiers),
+    InsertChar(char),
+    Paste,
+    SaveNotes,
+    // help popup
+    ScrollHelpDown,
+    ScrollHelpUp,
+    // Enter in an input, `y` in a popup
+    Submit,
+    // `n` in a popup
+    Decline,
+    // Esc, closes whatever is open
+    Cancel,
+}
+
+// the Normal mode keys that can't be rebound, in the order they're tried. an entry
+// with ctrl only matches with ctrl held, the others match whatever the modifiers.
+// the help is built from this, so it always shows the keys that really work
+const NORMAL_KEYS: &[(KeyCode, KeyModifiers, Action)] = &[
+    (KeyCode::Char('G'), KeyModifiers::NONE, Action::SelectLast),
+    (
+        KeyCode::Char('n'),
+        KeyModifiers::NONE,
+        Action::NextIncomplete,
+    ),
+    (
+        KeyCode::Char('N'),
+        KeyModifiers::NONE,
+        Action::PreviousIncomplete,
+    ),
+    (KeyCode::Char('f'), KeyModifiers::CONTROL, Action::PageDown),
+    (KeyCode::Char('b'), KeyModifiers::CONTROL, Action::PageUp),
+    (KeyCode::PageDown, KeyModifiers::NONE, Action::PageDown),
+    (KeyCode::PageUp, KeyModifiers::NONE, Action::PageUp),
+    (
+        KeyCode::Char(' '),
+        KeyModifiers::NONE,
+        Action::ToggleComplete,
+    ),
+    (KeyCode::Char('A'), KeyModifiers::NONE, Action::ToggleAll),
+    (KeyCode::Char('v'), KeyModifiers::NONE, Action::ToggleMark),
+    (
+        KeyCode::Char('a'),
+        KeyModifiers::CONTROL,
+        Action::MarkAllShown,
+    ),
+    (KeyCode::Char('o'), KeyModifiers::NONE, Action::AddSubtask),
+    (KeyCode::Char('h'), KeyModifiers::NONE, Action::Collapse),
+    (KeyCode::Left, KeyModifiers::NONE, Action::Collapse),
+    (
+        KeyCode::Char('l'),
+        KeyModifiers::CONTROL,
+        Action::ClearSearch,
+    ),
+    (KeyCode::Char('l'), KeyModifiers::NONE, Action::Expand),
+    (KeyCode::Right, KeyModifiers::NONE, Action::Expand),
+    (
+        KeyCode::Char('+'),
+        KeyModifiers::NONE,
+        Action::RaisePriority,
+    ),
+    (
+        KeyCode::Char('-'),
+        KeyModifiers::NONE,
+        Action::LowerPriority,
+    ),
+    (KeyCode::Char('J'), KeyModifiers::NONE, Action::MoveTodoDown),
+    (KeyCode::Char('K'), KeyModifiers::NONE, Action::MoveTodoUp),
+    (KeyCode::Char('s'), KeyModifiers::NONE, Action::CycleSort),
+    (KeyCode::Char('t'), KeyModifiers::NONE, Action::FilterByTag),
+    (
+        KeyCode::Char('f'),
+        KeyModifiers::NONE,
+        Action::CycleStatusFilter,
+    ),
+    (
+        KeyCode::Char('c'),
+        KeyModifiers::NONE,
+        Action::CycleAgeFilter,
+    ),
+    (
+        KeyCode::Char('H'),
+        KeyModifiers::NONE,
+        Action::ToggleGroupByDate,
+    ),
+    (KeyCode::Char('u'), KeyModifiers::NONE, Action::Undo),
+    (KeyCode::Char('r'), KeyModifiers::CONTROL, Action::Redo),
+    (KeyCode::Char('D'), KeyModifiers::NONE, Action::ClearAll),
+    (
+        KeyCode::Char('X'),
+        KeyModifiers::NONE,
+        Action::ArchiveCompleted,
+    ),
+    (KeyCode::Char('V'), KeyModifiers::NONE, Action::ToggleView),
+    (KeyCode::Char('E'), KeyModifiers::NONE, Action::EmptyTrash),
+    (KeyCode::Tab, KeyModifiers::NONE, Action::NextList),
+    (KeyCode::BackTab, KeyModifiers::NONE, Action::PreviousList),
+    (KeyCode::Enter, KeyModifiers::NONE, Action::ShowDetails),
+    (
+        KeyCode::Char('p'),
+        KeyModifiers::NONE,
+        Action::TogglePreview,
+    ),
+    (KeyCode::Char('e'), KeyModifiers::NONE, Action::EditNotes),
+    (KeyCode::Char('y'), KeyModifiers::CONTROL, Action::Copy),
+    (
+        KeyCode::Char('M'),
+        KeyModifiers::NONE,
+        Action::ExportMarkdown,
+    ),
+    (KeyCode::Char('T'), KeyModifiers::NONE, Action::ToggleTheme),
+    (KeyCode::Char(':'), KeyModifiers::NONE, Action::StartCommand),
+    (KeyCode::Char('?'), KeyModifiers::NONE, Action::ShowHelp),
+];
+
+impl Action {
+    // what the help says the Normal mode actions do
+    pub fn description(self) -> &'static str {
+        match self {
+            Action::MoveUp => "move up",
+            Action::MoveDown => "move down",
+            Action::Add => "add a todo",
+            Action::Delete => "delete the selected (or marked) todos",
+            Action::Edit => "edit the selected todo",
+            Action::Search => "search",
+            Action::Quit => "quit",
+            Action::SelectLast => "jump to the last todo",
+            Action::NextIncomplete => "jump to the next todo that isn't done",
+            Action::PreviousIncomplete => "jump to the previous todo that isn't done",
+            Action::PageDown => "move a page down",
+            Action::PageUp => "move a page up",
+            Action::ToggleComplete => "mark as done/not done (the marked todos if any)",
+            Action::ToggleAll => "mark all shown todos done/not done",
+            Action::ToggleMark => "mark/unmark for bulk delete or done",
+            Action::MarkAllShown => "mark everything a search/filter shows",
+            Action::AddSubtask => "add a subtask to the selected todo",
+            Action::Collapse => "collapse the subtasks",
+            Action::Expand => "expand the subtasks",
+            Action::ClearSearch => "clear the search",
+            Action::RaisePriority => "raise the priority (high goes round to low)",
+            Action::LowerPriority => "lower the priority (low goes round to high)",
+            Action::MoveTodoDown => "move the todo down",
+            Action::MoveTodoUp => "move the todo up",
+            Action::CycleSort => "cycle sort mode",
+            Action::FilterByTag => "filter by a tag",
+            Action::CycleStatusFilter => "show all/active/completed todos",
+            Action::CycleAgeFilter => "show todos created any time/today/this week",
+            Action::ToggleGroupByDate => "group todos under Today/Yesterday/Earlier",
+            Action::Undo => "undo",
+            Action::Redo => "redo",
+            Action::ClearAll => "delete all todos",
+            Action::ArchiveCompleted => "archive the done todos",
+            Action::ToggleView => "switch between the todos, archive and trash",
+            Action::EmptyTrash => "empty the trash (in the trash)",
+            Action::NextList => "switch to the next list",
+            Action::PreviousList => "switch to the previous list",
+            Action::ShowDetails => "show details",
+            Action::TogglePreview => "show/hide the preview pane",
+            Action::EditNotes => "edit the selected todo's notes",
+            Action::Copy => "copy the todo's text",
+            Action::ExportMarkdown => "export the list to todos.md",
+            Action::ToggleTheme => "toggle the theme",
+            Action::StartCommand => "run a command",
+            Action::ShowHelp => "this help",
+            _ => "",
+        }
+    }
+}
+
+// keys Normal mode handles itself, keys.toml can't take them. ctrl combinations
+// never go through the keymap so they can't clash
+pub fn is_fixed_key(code: KeyCode) -> bool {
+    matches!(code, KeyCode::Char('g' | '0'..='9'))
+        || NORMAL_KEYS.iter().any(|(bound, needed, _)| {
+            *bound == code && !needed.intersects(KeyModifiers::CONTROL | KeyModifiers::ALT)
+        })
+}
+
+// every Normal mode key with what it does, for the help popup
+pub fn normal_mode_help(keymap: &Keymap) -> Vec<(String, &'static str)> {
+    let mut help: Vec<(String, &'static str)> = [
+        Action::MoveDown,
+        Action::MoveUp,
+        Action::Add,
+        Action::Edit,
+        Action::Delete,
+        Action::Search,
+        Action::Quit,
+    ]
+    .into_iter()
+    .map(|action| (keymap.label(action), action.description()))
+    .collect();
+    // the keys that take a number or depend on where you are
+    help.extend([
+        ("gg".to_string(), "jump to the first todo"),
+        ("1/2/3".to_string(), "set priority to low/medium/high"),
+        (
+            "<number>G".to_string(),
+            "jump to the todo with that number (0 first for 1-3, 012G)",
+        ),
+        ("alt+<number>".to_string(), "switch to that list"),
+        ("r (archive/trash)".to_string(), "restore the todo"),
+        ("Esc (with marks)".to_string(), "clear the marks"),
+    ]);
+    let mut fixed: Vec<(Action, Vec<String>)> = Vec::new();
+    for (code, modifiers, action) in NORMAL_KEYS {
+        let mut name = key_name(*code);
+        if modifiers.contains(KeyModifiers::CONTROL) {
+            name = format!("ctrl+{}", name);
+        }
+        match fixed.iter_mut().find(|(bound, _)| bound == action) {
+            Some((_, names)) => names.push(name),
+            None => fixed.push((*action, vec![name])),
+        }
+    }
+    help.extend(
+        fixed
+            .into_iter()
+            .map(|(action, names)| (names.join("/"), action.description())),
+    );
+    help
+}
+
+impl App {
+    // a key press: what it's bound to in the current mode, any other key cancels a
+    // pending `gg` or count
+    pub fn handle_key(&mut self, code: KeyCode, modifiers: KeyModifiers) {
+        match self.key_action(code, modifiers) {
+            Some(action) => self.apply(action),
+            None => {
+                self.pending_g = false;
+                self.pending_count = None;
+            }
+        }
+    }
+
+    pub fn key_action(&self, code: KeyCode, modifiers: KeyModifiers) -> Option<Action> {
+        let ctrl = modifiers.contains(KeyModifiers::CONTROL);
+        // shift is part of typing a capital, ctrl and alt aren't
+        let typing = modifiers.difference(KeyModifiers::SHIFT).is_empty();
+        match self.input_mode {
+            InputMode::Normal => self.normal_key_action(code, modifiers),
+            InputMode::Searching => match code {
+                // both keep the filter, ctrl+l is what clears it
+                KeyCode::Enter | KeyCode::Esc => Some(Action::Cancel),
+                KeyCode::Char('l') if ctrl => Some(Action::ClearSearch),
+                KeyCode::Char('s') if ctrl => Some(Action::ToggleCaseSensitive),
+                KeyCode::Char('f') if ctrl => Some(Action::ToggleSearchMode),
+                KeyCode::Char('w') if ctrl => Some(Action::ToggleWholeWord),
+                KeyCode::Char(_) if typing => Some(Action::SearchInput(code)),
+                KeyCode::Backspace
+                | KeyCode::Delete
+                | KeyCode::Left
+                | KeyCode::Right
+                | KeyCode::Home
+                | KeyCode::End => Some(Action::SearchInput(code)),
+                _ => None,
+            },
+            InputMode::Adding | InputMode::Editing | InputMode::Command => match code {
+                KeyCode::Enter => Some(Action::Submit),
+                KeyCode::Esc => Some(Action::Cancel),
+                KeyCode::Char('v') if ctrl => Some(Action::Paste),
+                _ => Some(Action::Input(code, modifiers)),
+            },
+            InputMode::EditingNotes => match code {
+                KeyCode::Enter => Some(Action::InsertChar('\n')),
+                KeyCode::Char('s') if ctrl => Some(Action::SaveNotes),
+                KeyCode::Esc => Some(Action::Cancel),
+                KeyCode::Char('v') if ctrl => Some(Action::Paste),
+                _ => Some(Action::Input(code, modifiers)),
+            },
+            InputMode::Confirming => match code {
+                KeyCode::Char('y') | KeyCode::Enter => Some(Action::Submit),
+                KeyCode::Char('n') | KeyCode::Esc => Some(Action::Cancel),
+                _ => None,
+            },
+            // y/n like the other popups, s/d for save and discard
+            InputMode::QuitConfirm => match code {
+                KeyCode::Char('s' | 'y') => Some(Action::Submit),
+                KeyCode::Char('d' | 'n') => Some(Action::Decline),
+                KeyCode::Char('c') | KeyCode::Esc => Some(Action::Cancel),
+                _ => None,
+            },
+            InputMode::DiscardConfirm
+            | InputMode::ClearAllConfirm
+            | InputMode::EmptyTrashConfirm => match code {
+                KeyCode::Char('y') => Some(Action::Submit),
+                KeyCode::Char('n') | KeyCode::Esc => Some(Action::Cancel),
+                _ => None,
+            },
+            // any key closes the details popup
+            InputMode::Details => Some(Action::Cancel),
+            InputMode::Help => match code {
+                KeyCode::Char('j') | KeyCode::Down => Some(Action::ScrollHelpDown),
+                KeyCode::Char('k') | KeyCode::Up => Some(Action::ScrollHelpUp),
+                _ => Some(Action::Cancel),
+            },
+        }
+    }
+
+    fn normal_key_action(&self, code: KeyCode, modifiers: KeyModifiers) -> Option<Action> {
+        let alt = modifiers.contains(KeyModifiers::ALT);
+        match self.keymap.action(code, modifiers) {
+            // the archive and the trash can only be browsed and restored from
+            _ if self.view != View::Active && code == KeyCode::Char('r') => Some(Action::Restore),
+            Some(Action::Add | Action::Edit | Action::Delete) if self.view != View::Active => None,
+            // Esc drops the marks before it quits
+            Some(Action::Quit) if code == KeyCode::Esc && !self.marked.is_empty() => {
+                Some(Action::ClearMarks)
+            }
+            Some(action) => Some(action),
+            // keys that can't be rebound (yet)
+            None => match code {
+                KeyCode::Char('g') => Some(Action::G),
+                // 1/2/3 set the priority unless they're part of a count, so a count
+                // that starts with them needs a 0 in front
+                KeyCode::Char('1') if self.pending_count.is_none() && !alt => {
+                    Some(Action::SetPriority(Priority::Low))
+                }
+                KeyCode::Char('2') if self.pending_count.is_none() && !alt => {
+                    Some(Action::SetPriority(Priority::Medium))
+                }
+                KeyCode::Char('3') if self.pending_count.is_none() && !alt => {
+                    Some(Action::SetPriority(Priority::High))
+                }
+                KeyCode::Char(digit @ '0'..='9') if !alt => digit
+                    .to_digit(10)
+                    .map(|digit| Action::Digit(digit as usize)),
+                // plain digits are the count for G
+                KeyCode::Char(digit @ '1'..='9') if alt => {
+                    Some(Action::SwitchList(digit as usize - '1' as usize))
+                }
+                _ => NORMAL_KEYS
+                    .iter()
+                    .find(|(bound, needed, _)| *bound == code && modifiers.contains(*needed))
+                    .map(|(_, _, action)| *action),
+            },
+        }
+    }
+
+    pub fn apply(&mut self, action: Action) {
+        // anything but a second `g` cancels a pending `gg`, and anything but another
+        // digit cancels a count
+        let pending_g = mem::take(&mut self.pending_g);
+        let count = self.pending_count.take();
+        match action {
+            Action::MoveUp => self.move_selection_up(),
+            Action::MoveDown => self.move_selection_down(),
+            Action::Add => self.start_adding(),
+            Action::Delete => self.start_delete_confirmation(),
+            Action::Edit => {
+                if self.selected_index.is_some() {
+                    self.start_editing();
+                }
+            }
+            Action::Search => self.start_search(),
+            Action::Quit => {
+                if self.save_on_quit() {
+                    self.should_quit = true;
+                } else {
+                    // the error is in the status bar, let the user decide
+                    self.input_mode = InputMode::QuitConfirm;
+                }
+            }
+            Action::ClearMarks => self.marked.clear(),
+            Action::Restore => self.restore_selected(),
+            Action::G if pending_g => self.select_first(),
+            Action::G => self.pending_g = true,
+            Action::Digit(digit) => {
+                self.pending_count =
+                    Some(count.unwrap_or(0).saturating_mul(10).saturating_add(digit));
+            }
+            Action::SelectLast => match count {
+                Some(number) => self.jump_to(number),
+                None => self.select_last(),
+            },
+            Action::PageUp => self.page_up(),
+            Action::PageDown => self.page_down(),
+            Action::NextIncomplete => self.select_next_incomplete(),
+            Action::PreviousIncomplete => self.select_prev_incomplete(),
+            Action::ToggleComplete if !self.marked.is_empty() => self.toggle_marked(),
+            Action::ToggleComplete => self.toggle_selected(),
+            Action::ToggleAll => self.toggle_all(),
+            Action::ToggleMark => self.toggle_mark(),
+            // only with a filter, marking every todo is rarely what you want
+            Action::MarkAllShown if self.is_filtered() => self.mark_all_shown(),
+            Action::MarkAllShown => {}
+            Action::ShowDetails => self.show_details(),
+            Action::ShowHelp => self.show_help(),
+            Action::SetPriority(priority) => self.set_selected_priority(priority),
+            Action::RaisePriority => self.raise_selected_priority(),
+            Action::LowerPriority => self.lower_selected_priority(),
+            Action::CycleSort => self.cycle_sort_mode(),
+            Action::FilterByTag => self.start_tag_search(),
+            Action::ExportMarkdown => self.export_markdown_to_default(),
+            Action::CycleStatusFilter => self.cycle_status_filter(),
+            Action::CycleAgeFilter => self.cycle_age_filter(),
+            Action::ToggleGroupByDate => self.toggle_group_by_date(),
+            Action::ClearSearch => {
+                self.clear_search();
+                self.input_mode = InputMode::Normal;
+            }
+            Action::MoveTodoUp => self.move_todo_up(),
+            Action::MoveTodoDown => self.move_todo_down(),
+            Action::AddSubtask => self.start_adding_subtask(),
+            Action::EditNotes if self.view == View::Active => self.start_editing_notes(),
+            Action::EditNotes => {}
+            Action::Collapse => self.set_selected_collapsed(true),
+            Action::Expand => self.set_selected_collapsed(false),
+            Action::Undo => self.undo(),
+            Action::Redo => self.redo(),
+            Action::Copy => self.copy_selected(),
+            Action::ToggleTheme => self.toggle_theme(),
+            Action::TogglePreview => self.toggle_preview(),
+            Action::ToggleView => self.toggle_view(),
+            Action::ArchiveCompleted => self.archive_completed(),
+            Action::ClearAll => self.start_clear_all_confirmation(),
+            Action::EmptyTrash => self.start_empty_trash_confirmation(),
+            Action::NextList => self.next_list(),
+            Action::PreviousList => self.previous_list(),
+            Action::SwitchList(index) => self.switch_list(index),
+            Action::StartCommand => self.start_command(),
+            Action::SearchInput(code) => self.handle_search_key(code),
+            Action::ToggleCaseSensitive => self.toggle_case_sensitive(),
+            Action::ToggleSearchMode => self.toggle_search_mode(),
+            Action::ToggleWholeWord => self.toggle_whole_word(),
+            Action::Input(code, modifiers) => self.handle_input_key(code, modifiers),
+            Action::InsertChar(c) => self.insert_char(c),
+            Action::Paste => self.paste(),
+            Action::SaveNotes => self.save_notes(),
+            Action::ScrollHelpDown => self.help_scroll = self.help_scroll.saturating_add(1),
+            Action::ScrollHelpUp => self.help_scroll = self.help_scroll.saturating_sub(1),
+            Action::Submit => self.submit(),
+            Action::Decline => self.decline(),
+            Action::Cancel => self.cancel(),
+        }
+    }
+
+    // Enter or `y`, depending on what's open
+    fn submit(&mut self) {
+        match self.input_mode {
+            InputMode::Adding => {
+                self.add_todo();
+                self.input_mode = InputMode::Normal;
+            }
+            InputMode::Editing => self.save_edit(),
+            InputMode::Command => {
+                let command = mem::take(&mut self.input_buffer);
+                self.cursor_pos = 0;
+                self.input_mode = InputMode::Normal;
+                self.run_command(&command);
+            }
+            InputMode::Confirming => {
+                if self.marked.is_empty() {
+                    self.delete_selected_todo();
+                } else {
+                    self.delete_marked_todos();
+                }
+                self.cancel_delete();
+            }
+            InputMode::QuitConfirm => {
+                if self.save_to_disk() {
+                    self.should_quit = true;
+                } else {
+                    // saving failed, stay so the error can be read
+                    self.input_mode = InputMode::Normal;
+                }
+            }
+            InputMode::DiscardConfirm => self.cancel_edit(),
+            InputMode::ClearAllConfirm => {
+                self.clear_all();
+                self.input_mode = InputMode::Normal;
+            }
+            InputMode::EmptyTrashConfirm => {
+                self.empty_trash();
+                self.input_mode = InputMode::Normal;
+            }
+            _ => {}
+        }
+    }
+
+    // `n` when quitting, leave without saving
+    fn decline(&mut self) {
+        match self.input_mode {
+            InputMode::QuitConfirm => self.should_quit = true,
+            _ => self.cancel(),
+        }
+    }
+
+    fn cancel(&mut self) {
+        match self.input_mode {
+            InputMode::Adding | InputMode::Editing | InputMode::EditingNotes => self.cancel_input(),
+            InputMode::Command => {
+                self.input_mode = InputMode::Normal;
+                self.input_buffer.clear();
+                self.cursor_pos = 0;
+            }
+            InputMode::Confirming => self.cancel_delete(),
+            InputMode::DiscardConfirm => self.keep_editing(),
+            _ => self.input_mode = InputMode::Normal,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::app::Severity;
+
+    fn app_with(texts: &[&str]) -> App {
+        App::from_lines(texts.iter().map(|text| text.to_string()))
+    }
+
+    fn press(app: &mut App, keys: &str) {
+        for c in keys.chars() {
+            app.handle_key(KeyCode::Char(c), KeyModifiers::NONE);
+        }
+    }
+
+    #[test]
+    fn todos_can_be_added_and_completed_through_actions() {
+        let mut app = app_with(&["one"]);
+        app.apply(Action::Add);
+        for c in "two".chars() {
+            app.apply(Action::Input(KeyCode::Char(c), KeyModifiers::NONE));
+        }
+        app.apply(Action::Submit);
+        assert!(matches!(app.input_mode, InputMode::Normal));
+        assert_eq!(app.todos[1].text, "two");
+
+        app.apply(Action::MoveDown);
+        app.apply(Action::ToggleComplete);
+        assert!(app.todos[1].done);
+
+        app.apply(Action::Delete);
+        app.apply(Action::Submit);
+        assert_eq!(app.todos.len(), 1);
+    }
+
+    #[test]
+    fn counts_and_gg_build_up_over_several_keys() {
+        let mut app = app_with(&["one", "two", "three", "four", "five"]);
+        press(&mut app, "4G");
+        assert_eq!(app.selected_index, Some(3));
+        press(&mut app, "gg");
+        assert_eq!(app.selected_index, Some(0));
+        // 1/2/3 are the priority keys, a 0 in front makes them a count
+        press(&mut app, "03G");
+        assert_eq!(app.selected_index, Some(2));
+        assert!(app.todos[2].priority == Priority::default());
+        // another key in between cancels the count
+        press(&mut app, "4xG");
+        assert_eq!(app.selected_index, Some(4));
+    }
+
+    #[test]
+    fn one_two_three_set_the_priority() {
+        let mut app = app_with(&["one"]);
+        press(&mut app, "3");
+        assert!(app.todos[0].priority == Priority::High);
+        press(&mut app, "1");
+        assert!(app.todos[0].priority == Priority::Low);
+        press(&mut app, "2");
+        assert!(app.todos[0].priority == Priority::Medium);
+        assert_eq!(app.pending_count, None);
+    }
+
+    #[test]
+    fn the_same_key_does_different_things_by_mode() {
+        let mut app = app_with(&["one"]);
+        press(&mut app, "/n");
+        assert_eq!(app.search_input, "n");
+        app.handle_key(KeyCode::Esc, KeyModifiers::NONE);
+        assert!(matches!(app.input_mode, InputMode::Normal));
+        // Esc keeps the search, in Normal mode it would have quit
+        assert_eq!(app.search_input, "n");
+        assert!(!app.should_quit);
+    }
+
+    #[test]
+    fn the_help_lists_every_key_the_table_handles() {
+        let app = app_with(&["one"]);
+        let help = normal_mode_help(&app.keymap);
+        for (code, modifiers, action) in NORMAL_KEYS {
+            // the table and the handling are the same thing
+            assert_eq!(app.normal_key_action(*code, *modifiers), Some(*action));
+            assert!(!action.description().is_empty(), "{:?}", action);
+            let name = key_name(*code);
+            assert!(
+                help.iter().any(|(keys, description)| {
+                    keys.split('/').any(|key| key.ends_with(&name))
+                        && *description == action.description()
+                }),
+                "{} is missing from the help",
+                name
+            );
+        }
+    }
+
+    #[test]
+    fn the_search_box_edits_at_the_cursor() {
+        let mut app = app_with(&["abc", "bc"]);
+        press(&mut app, "/aé");
+        app.handle_key(KeyCode::Left, KeyModifiers::NONE);
+        press(&mut app, "b");
+        assert_eq!(app.search_input, "abé");
+        assert_eq!(app.cursor_pos, 2);
+
+        app.handle_key(KeyCode::Home, KeyModifiers::NONE);
+        app.handle_key(KeyCode::Delete, KeyModifiers::NONE);
+        app.handle_key(KeyCode::End, KeyModifiers::NONE);
+        app.handle_key(KeyCode::Backspace, KeyModifiers::NONE);
+        assert_eq!(app.search_input, "b");
+        assert_eq!(app.filtered_todos.len(), 2);
+
+        // coming back to the search puts the cursor after the text
+        app.handle_key(KeyCode::Enter, KeyModifiers::NONE);
+        press(&mut app, "/c");
+        assert_eq!(app.search_input, "bc");
+
+        // unbound ctrl and alt keys don't type, shift does
+        app.handle_key(KeyCode::Char('x'), KeyModifiers::CONTROL);
+        app.handle_key(KeyCode::Char('x'), KeyModifiers::ALT);
+        app.handle_key(KeyCode::Char('D'), KeyModifiers::SHIFT);
+        assert_eq!(app.search_input, "bcD");
+    }
+
+    #[test]
+    fn t_filters_by_the_tag_typed() {
+        let mut app = app_with(&["buy milk #shopping #urgent", "call mom", "fix bike #urgent"]);
+        press(&mut app, "t");
+        assert_eq!(app.search_input, "#");
+        // nothing typed yet, every tagged todo
+        assert_eq!(app.filtered_todos.len(), 2);
+        press(&mut app, "shopping");
+        assert_eq!(app.filtered_todos.len(), 1);
+        assert_eq!(app.shown_todos()[0].text, "buy milk");
+        assert_eq!(app.shown_todos()[0].tags, ["shopping", "urgent"]);
+    }
+
+    #[test]
+    fn e_writes_notes_over_several_lines_until_ctrl_s() {
+        let mut app = app_with(&["one"]);
+        press(&mut app, "efirst");
+        app.handle_key(KeyCode::Enter, KeyModifiers::NONE);
+        press(&mut app, "second");
+        assert!(matches!(app.input_mode, InputMode::EditingNotes));
+        app.handle_key(KeyCode::Char('s'), KeyModifiers::CONTROL);
+        assert!(matches!(app.input_mode, InputMode::Normal));
+        assert_eq!(app.todos[0].notes, "first\nsecond");
+
+        // and they go to the file with the todo
+        let json = serde_json::to_string(&app.todos[0]).unwrap();
+        let loaded: crate::app::Todo = serde_json::from_str(&json).unwrap();
+        assert_eq!(loaded.notes, "first\nsecond");
+    }
+
+    #[test]
+    fn quitting_with_unsaved_changes_asks_first() {
+        let mut app = app_with(&["one"]);
+        press(&mut app, "atwo");
+        app.handle_key(KeyCode::Enter, KeyModifiers::NONE);
+        assert!(app.dirty);
+
+        // there's nowhere to save to here, so q can't just save and go
+        press(&mut app, "q");
+        assert!(matches!(app.input_mode, InputMode::QuitConfirm));
+        app.handle_key(KeyCode::Esc, KeyModifiers::NONE);
+        assert!(matches!(app.input_mode, InputMode::Normal));
+        assert!(!app.should_quit);
+
+        // saving fails, so s stays with the error showing
+        press(&mut app, "qs");
+        assert!(!app.should_quit);
+        assert_eq!(app.message.as_ref().unwrap().severity, Severity::Error);
+
+        press(&mut app, "qd");
+        assert!(app.should_quit);
+    }
+}
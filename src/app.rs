@@ -0,0 +1,3820 @@
+use chrono::{DateTime, Datelike, Days, Local, NaiveDate};
+use crossterm::event::{KeyCode, KeyModifiers, MouseButton, MouseEvent, MouseEventKind};
+use fuzzy_matcher::{skim::SkimMatcherV2, FuzzyMatcher};
+use ratatui::{layout::Rect, widgets::ListState};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use std::{
+    borrow::Borrow,
+    cmp::Reverse,
+    collections::{HashMap, HashSet},
+    fmt, fs, io, mem,
+    ops::Range,
+    path::{Path, PathBuf},
+    time::{Duration, Instant},
+};
+
+use crate::{clipboard::Clipboard, keymap::Keymap, sqlite, symbols::Symbols, theme::Theme};
+
+#[derive(Default)]
+pub enum InputMode {
+    #[default]
+    Normal, // this will be our default mode
+    Editing,
+    // the multi-line notes of the selected todo
+    EditingNotes,
+    Searching,
+    Adding,
+    Confirming,
+    QuitConfirm,
+    EmptyTrashConfirm,
+    ClearAllConfirm,
+    // Esc with unsaved text in the add/edit input
+    DiscardConfirm,
+    // typing a `:` command
+    Command,
+    Details,
+    Help,
+}
+
+#[derive(Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Debug, Serialize, Deserialize)]
+pub enum Priority {
+    Low,
+    #[default]
+    Medium,
+    High,
+}
+
+impl Priority {
+    // `+` and `-` cycle, past high comes low again and the other way around
+    pub fn higher(self) -> Self {
+        match self {
+            Priority::Low => Priority::Medium,
+            Priority::Medium => Priority::High,
+            Priority::High => Priority::Low,
+        }
+    }
+
+    pub fn lower(self) -> Self {
+        match self {
+            Priority::Low => Priority::High,
+            Priority::Medium => Priority::Low,
+            Priority::High => Priority::Medium,
+        }
+    }
+
+    pub fn label(self) -> &'static str {
+        match self {
+            Priority::Low => "low",
+            Priority::Medium => "medium",
+            Priority::High => "high",
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum AppError {
+    Io(io::Error),
+    // the data file exists but isn't valid json
+    Parse(serde_json::Error),
+    Serialize(serde_json::Error),
+    TomlSerialize(toml::ser::Error),
+    // there's nowhere to save, the data file failed to load at startup or the
+    // todos came from a text file
+    NoDataFile,
+    // a config (or todos.toml) file that isn't valid toml
+    Toml(toml::de::Error),
+    // a key name in keys.toml we don't understand
+    InvalidKey(String),
+    // a key in keys.toml that would hide one that can't be rebound
+    FixedKey(String),
+    UnknownTheme(String),
+    // no clipboard to talk to, e.g. over ssh
+    Clipboard(String),
+    // todos.db couldn't be read or written
+    Sqlite(String),
+}
+
+impl fmt::Display for AppError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            AppError::Io(err) => write!(f, "{}", err),
+            AppError::Parse(err) => write!(f, "invalid todo file: {}", err),
+            AppError::Serialize(err) => write!(f, "could not serialize todos: {}", err),
+            AppError::TomlSerialize(err) => write!(f, "could not serialize todos: {}", err),
+            AppError::NoDataFile => write!(
+                f,
+                "no data file this session, it couldn't be loaded or the todos came from a text file"
+            ),
+            AppError::Toml(err) => write!(f, "invalid toml: {}", err),
+            AppError::InvalidKey(key) => write!(f, "unknown key {:?}", key),
+            AppError::FixedKey(key) => write!(f, "{:?} is already taken and can't be rebound", key),
+            AppError::UnknownTheme(name) => write!(f, "unknown theme {:?}", name),
+            AppError::Clipboard(err) => write!(f, "clipboard unavailable: {}", err),
+            AppError::Sqlite(err) => write!(f, "database error: {}", err),
+        }
+    }
+}
+
+impl std::error::Error for AppError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            AppError::Io(err) => Some(err),
+            AppError::Parse(err) | AppError::Serialize(err) => Some(err),
+            AppError::Toml(err) => Some(err),
+            AppError::TomlSerialize(err) => Some(err),
+            AppError::NoDataFile
+            | AppError::InvalidKey(_)
+            | AppError::FixedKey(_)
+            | AppError::UnknownTheme(_)
+            | AppError::Clipboard(_)
+            | AppError::Sqlite(_) => None,
+        }
+    }
+}
+
+impl From<io::Error> for AppError {
+    fn from(err: io::Error) -> Self {
+        AppError::Io(err)
+    }
+}
+
+// serialization failures are mapped explicitly, anything coming through `?` is a parse error
+impl From<serde_json::Error> for AppError {
+    fn from(err: serde_json::Error) -> Self {
+        AppError::Parse(err)
+    }
+}
+
+impl From<toml::de::Error> for AppError {
+    fn from(err: toml::de::Error) -> Self {
+        AppError::Toml(err)
+    }
+}
+
+// how long a status bar message stays up
+const MESSAGE_DURATION: Duration = Duration::from_secs(3);
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Severity {
+    Info,
+    Error,
+}
+
+// feedback like "Copied to the clipboard" or an error, in place of the key hints
+pub struct Message {
+    pub text: String,
+    pub severity: Severity,
+    pub shown_at: Instant,
+}
+
+// how many undo steps we keep around
+pub const MAX_UNDO_HISTORY: usize = 100;
+
+// changes are written once nothing else changed for this long
+const AUTOSAVE_DELAY: Duration = Duration::from_secs(1);
+
+// two clicks on the same row closer than this are a double-click
+const DOUBLE_CLICK_INTERVAL: Duration = Duration::from_millis(400);
+
+#[derive(Clone, Copy, Default, PartialEq)]
+pub enum SortMode {
+    Alphabetical,
+    // ids are handed out in creation order
+    #[default]
+    CreatedAt,
+    Priority,
+    Completed,
+    // whatever order J/K left the todos in
+    Manual,
+}
+
+impl SortMode {
+    pub fn next(self) -> Self {
+        match self {
+            SortMode::CreatedAt => SortMode::Alphabetical,
+            SortMode::Alphabetical => SortMode::Priority,
+            SortMode::Priority => SortMode::Completed,
+            SortMode::Completed => SortMode::Manual,
+            SortMode::Manual => SortMode::CreatedAt,
+        }
+    }
+
+    // the mode whose label is `name`, for `:sort <name>`
+    pub fn from_label(name: &str) -> Option<Self> {
+        [
+            SortMode::Alphabetical,
+            SortMode::CreatedAt,
+            SortMode::Priority,
+            SortMode::Completed,
+            SortMode::Manual,
+        ]
+        .into_iter()
+        .find(|mode| mode.label() == name)
+    }
+
+    pub fn label(self) -> &'static str {
+        match self {
+            SortMode::Alphabetical => "alphabetical",
+            SortMode::CreatedAt => "created",
+            SortMode::Priority => "priority",
+            SortMode::Completed => "completed",
+            SortMode::Manual => "manual",
+        }
+    }
+}
+
+#[derive(Clone, Copy, Default, PartialEq)]
+pub enum SearchMode {
+    // plain `contains`, predictable so it's the default
+    #[default]
+    Substring,
+    Fuzzy,
+}
+
+impl SearchMode {
+    pub fn next(self) -> Self {
+        match self {
+            SearchMode::Substring => SearchMode::Fuzzy,
+            SearchMode::Fuzzy => SearchMode::Substring,
+        }
+    }
+}
+
+// which todos are shown by completion, on top of the search
+#[derive(Clone, Copy, Default, PartialEq)]
+pub enum StatusFilter {
+    #[default]
+    All,
+    Active,
+    Completed,
+}
+
+impl StatusFilter {
+    pub fn next(self) -> Self {
+        match self {
+            StatusFilter::All => StatusFilter::Active,
+            StatusFilter::Active => StatusFilter::Completed,
+            StatusFilter::Completed => StatusFilter::All,
+        }
+    }
+
+    pub fn label(self) -> &'static str {
+        match self {
+            StatusFilter::All => "All",
+            StatusFilter::Active => "Active",
+            StatusFilter::Completed => "Completed",
+        }
+    }
+
+    fn matches(self, todo: &Todo) -> bool {
+        match self {
+            StatusFilter::All => true,
+            StatusFilter::Active => !todo.done,
+            StatusFilter::Completed => todo.done,
+        }
+    }
+}
+
+// which todos are shown by when they were created, on top of the other filters
+#[derive(Clone, Copy, Default, PartialEq)]
+pub enum AgeFilter {
+    #[default]
+    All,
+    Today,
+    // monday to sunday, like the calendar
+    ThisWeek,
+}
+
+impl AgeFilter {
+    pub fn next(self) -> Self {
+        match self {
+            AgeFilter::All => AgeFilter::Today,
+            AgeFilter::Today => AgeFilter::ThisWeek,
+            AgeFilter::ThisWeek => AgeFilter::All,
+        }
+    }
+
+    pub fn label(self) -> &'static str {
+        match self {
+            AgeFilter::All => "Any time",
+            AgeFilter::Today => "Today",
+            AgeFilter::ThisWeek => "This week",
+        }
+    }
+
+    fn matches(self, todo: &Todo, today: NaiveDate) -> bool {
+        let created = todo.created_at.date_naive();
+        match self {
+            AgeFilter::All => true,
+            AgeFilter::Today => created == today,
+            AgeFilter::ThisWeek => created.iso_week() == today.iso_week(),
+        }
+    }
+}
+
+// the headers the list is split under when grouping by date
+#[derive(Clone, Copy, PartialEq, PartialOrd, Eq, Ord, Debug)]
+pub enum DateGroup {
+    Today,
+    Yesterday,
+    Earlier,
+}
+
+impl DateGroup {
+    fn of(todo: &Todo, today: NaiveDate) -> Self {
+        let created = todo.created_at.date_naive();
+        if created >= today {
+            DateGroup::Today
+        } else if today.checked_sub_days(Days::new(1)) == Some(created) {
+            DateGroup::Yesterday
+        } else {
+            DateGroup::Earlier
+        }
+    }
+
+    pub fn label(self) -> &'static str {
+        match self {
+            DateGroup::Today => "Today",
+            DateGroup::Yesterday => "Yesterday",
+            DateGroup::Earlier => "Earlier",
+        }
+    }
+}
+
+// which list the todo list shows
+#[derive(Clone, Copy, Default, PartialEq)]
+pub enum View {
+    #[default]
+    Active,
+    Archived,
+    // deleted todos, only kept for the session
+    Trash,
+}
+
+impl View {
+    pub fn next(self) -> Self {
+        match self {
+            View::Active => View::Archived,
+            View::Archived => View::Trash,
+            View::Trash => View::Active,
+        }
+    }
+
+    pub fn title(self) -> &'static str {
+        match self {
+            View::Active => "Todos",
+            View::Archived => "Archive",
+            View::Trash => "Trash",
+        }
+    }
+}
+
+#[derive(Clone, PartialEq, Serialize, Deserialize)]
+pub struct Todo {
+    // stable identity, so duplicate texts don't get mixed up
+    #[serde(default)]
+    pub id: u64,
+    pub text: String,
+    #[serde(default)]
+    pub done: bool,
+    #[serde(default)]
+    pub priority: Priority,
+    // `#tag` words typed in the text, stored without the `#`
+    #[serde(default)]
+    pub tags: Vec<String>,
+    // files from before timestamps get the file's mtime instead, see `fill_timestamps`
+    #[serde(default = "missing_timestamp")]
+    pub created_at: DateTime<Local>,
+    #[serde(default = "missing_timestamp")]
+    pub updated_at: DateTime<Local>,
+    // subtasks, which can have subtasks of their own
+    #[serde(default)]
+    pub children: Vec<Todo>,
+    // children are hidden while collapsed
+    #[serde(default)]
+    pub collapsed: bool,
+    // free text that can span several lines, shown in the preview pane
+    #[serde(default)]
+    pub notes: String,
+}
+
+impl Todo {
+    pub fn new(id: u64, text: String) -> Self {
+        let now = Local::now();
+        Self {
+            id,
+            text,
+            done: false,
+            priority: Priority::default(),
+            tags: Vec::new(),
+            created_at: now,
+            updated_at: now,
+            children: Vec::new(),
+            collapsed: false,
+            notes: String::new(),
+        }
+    }
+
+    fn touch(&mut self) {
+        self.updated_at = Local::now();
+    }
+
+    // completing a todo completes its subtasks too
+    fn set_done(&mut self, done: bool) {
+        self.done = done;
+        self.touch();
+        for child in &mut self.children {
+            child.set_done(done);
+        }
+    }
+
+    // (done, total) of the direct subtasks, `None` without any
+    pub fn progress(&self) -> Option<(usize, usize)> {
+        if self.children.is_empty() {
+            return None;
+        }
+        let done = self.children.iter().filter(|child| child.done).count();
+        Some((done, self.children.len()))
+    }
+
+    // the text as the user would type it, tags included
+    pub fn input_text(&self) -> String {
+        let mut text = self.text.clone();
+        for tag in &self.tags {
+            text.push_str(" #");
+            text.push_str(tag);
+        }
+        text
+    }
+}
+
+// the lists as they were before a change, for undo/redo
+#[derive(Clone)]
+pub struct Snapshot {
+    todos: Vec<Todo>,
+    archived: Vec<Todo>,
+    trash: Vec<Todo>,
+}
+
+// how the data files are written, picked by the data file's extension
+#[derive(Clone, Copy, Default, PartialEq, Debug, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Format {
+    #[default]
+    Json,
+    // diffs nicely when the todos are kept in version control
+    Toml,
+    // a database instead of files, needs the sqlite feature
+    Sqlite,
+}
+
+impl Format {
+    pub fn from_path(path: &Path) -> Self {
+        match path.extension().and_then(|ext| ext.to_str()) {
+            Some("toml") => Format::Toml,
+            Some("db" | "sqlite") => Format::Sqlite,
+            _ => Format::Json,
+        }
+    }
+
+    pub fn extension(self) -> &'static str {
+        match self {
+            Format::Json => "json",
+            Format::Toml => "toml",
+            Format::Sqlite => "db",
+        }
+    }
+
+    fn serialize<T: Serialize>(self, value: &T) -> Result<String, AppError> {
+        match self {
+            Format::Json => serde_json::to_string_pretty(value).map_err(AppError::Serialize),
+            Format::Toml => toml::to_string_pretty(value).map_err(AppError::TomlSerialize),
+            Format::Sqlite => Err(AppError::Sqlite("not a file format".to_string())),
+        }
+    }
+
+    fn deserialize<T: DeserializeOwned>(self, contents: &str) -> Result<T, AppError> {
+        match self {
+            Format::Json => Ok(serde_json::from_str(contents)?),
+            Format::Toml => Ok(toml::from_str(contents)?),
+            Format::Sqlite => Err(AppError::Sqlite("not a file format".to_string())),
+        }
+    }
+}
+
+// archived todos are kept next to the data file and named after it, todos.json's
+// in todos.archive.json, so two data files in one directory don't share an archive
+pub fn archive_path(data_path: &Path) -> PathBuf {
+    let stem = data_path.file_stem().unwrap_or_default().to_string_lossy();
+    data_path.with_file_name(format!(
+        "{}.archive.{}",
+        stem,
+        Format::from_path(data_path).extension()
+    ))
+}
+
+// the archive of todos.json used to be archive.json, it's still read until the
+// next save writes todos.archive.json
+fn read_archive(data_path: &Path) -> Result<Vec<Todo>, AppError> {
+    let path = archive_path(data_path);
+    let old_path = data_path.with_file_name(format!(
+        "archive.{}",
+        Format::from_path(data_path).extension()
+    ));
+    if !path.exists() && data_path.file_stem() == Some("todos".as_ref()) && old_path.exists() {
+        return read_todos(&old_path);
+    }
+    read_todos(&path)
+}
+
+// where a todo lives in the database
+const ARCHIVE_PLACE: &str = "archive";
+
+fn list_place(index: usize) -> String {
+    format!("list:{}", index)
+}
+
+// a toml file can't be just an array, so the archive is `todos = [...]` there
+#[derive(Default, Serialize, Deserialize)]
+struct TodosFile {
+    #[serde(default)]
+    todos: Vec<Todo>,
+}
+
+// a named list of todos, shown as a tab
+#[derive(Clone, Default, Serialize, Deserialize)]
+pub struct TodoList {
+    pub name: String,
+    // empty for the active list, its todos live in `App::todos` while it's active
+    #[serde(default)]
+    pub todos: Vec<Todo>,
+}
+
+impl TodoList {
+    fn new(name: &str) -> Self {
+        Self {
+            name: name.to_string(),
+            todos: Vec::new(),
+        }
+    }
+}
+
+// the name of the list you get when there's only one
+const DEFAULT_LIST_NAME: &str = "Todos";
+
+// what todos.json holds: the lists plus where the user left off
+#[derive(Default, Serialize, Deserialize)]
+struct DataFile {
+    // files from before named lists only had the one list of todos
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    todos: Vec<Todo>,
+    #[serde(default)]
+    lists: Vec<TodoList>,
+    #[serde(default)]
+    active_list: usize,
+    #[serde(default)]
+    selected_id: Option<u64>,
+    // fallback for when the selected todo is gone
+    #[serde(default)]
+    selected_index: Option<usize>,
+    #[serde(default)]
+    scroll_offset: usize,
+}
+
+// older versions wrote just the array of todos
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum StoredData {
+    Todos(Vec<Todo>),
+    DataFile(DataFile),
+}
+
+// stands in for a timestamp the file didn't have until the load replaces it
+fn missing_timestamp() -> DateTime<Local> {
+    DateTime::UNIX_EPOCH.with_timezone(&Local)
+}
+
+// todos saved before they had timestamps were created some time before the file was
+// last written, the mtime is the best guess there is
+fn fill_timestamps(todos: &mut [Todo], path: &Path) {
+    let modified = fs::metadata(path)
+        .and_then(|metadata| metadata.modified())
+        .map(DateTime::<Local>::from)
+        .unwrap_or_else(|_| Local::now());
+    fn walk(todos: &mut [Todo], modified: DateTime<Local>) {
+        for todo in todos {
+            if todo.created_at == missing_timestamp() {
+                todo.created_at = modified;
+            }
+            if todo.updated_at == missing_timestamp() {
+                todo.updated_at = todo.created_at;
+            }
+            walk(&mut todo.children, modified);
+        }
+    }
+    walk(todos, modified);
+}
+
+fn read_data_file(path: &Path) -> Result<DataFile, AppError> {
+    match fs::read_to_string(path) {
+        Ok(contents) => {
+            let mut data = parse_data_file(Format::from_path(path), &contents)?;
+            fill_timestamps(&mut data.todos, path);
+            for list in &mut data.lists {
+                fill_timestamps(&mut list.todos, path);
+            }
+            Ok(data)
+        }
+        Err(err) if err.kind() == io::ErrorKind::NotFound => Ok(DataFile::default()),
+        Err(err) => Err(err.into()),
+    }
+}
+
+fn parse_data_file(format: Format, contents: &str) -> Result<DataFile, AppError> {
+    // toml support came later, there are no old toml files
+    if format == Format::Toml {
+        return format.deserialize(contents);
+    }
+    match format.deserialize(contents)? {
+        StoredData::Todos(todos) => Ok(DataFile {
+            todos,
+            ..Default::default()
+        }),
+        StoredData::DataFile(data) => Ok(data),
+    }
+}
+
+// the todos of a file to import: a todos.json or todos.toml (every list in it), or
+// plain text with one todo per line
+pub fn read_import(path: &Path) -> Result<Vec<Todo>, AppError> {
+    let contents = fs::read_to_string(path)?;
+    let format = match path.extension().and_then(|extension| extension.to_str()) {
+        Some("json") => Format::Json,
+        Some("toml") => Format::Toml,
+        _ => return Ok(todos_from_lines(&contents)),
+    };
+    let mut data = parse_data_file(format, &contents)?;
+    let mut todos = mem::take(&mut data.todos);
+    for list in data.lists {
+        todos.extend(list.todos);
+    }
+    fill_timestamps(&mut todos, path);
+    Ok(todos)
+}
+
+// ids are left at 0, they're handed out when the todos are added to an app
+fn todos_from_lines(contents: &str) -> Vec<Todo> {
+    contents
+        .lines()
+        .filter_map(|line| {
+            let (text, tags) = parse_tags(line);
+            if text.is_empty() {
+                return None;
+            }
+            let mut todo = Todo::new(0, text);
+            todo.tags = tags;
+            Some(todo)
+        })
+        .collect()
+}
+
+fn read_todos(path: &Path) -> Result<Vec<Todo>, AppError> {
+    match fs::read_to_string(path) {
+        Ok(contents) => {
+            let mut todos: Vec<Todo> = match Format::from_path(path) {
+                Format::Toml => Format::Toml.deserialize::<TodosFile>(&contents)?.todos,
+                format => format.deserialize(&contents)?,
+            };
+            fill_timestamps(&mut todos, path);
+            Ok(todos)
+        }
+        // first run, start with an empty list
+        Err(err) if err.kind() == io::ErrorKind::NotFound => Ok(Vec::new()),
+        Err(err) => Err(err.into()),
+    }
+}
+
+// the todos in display order with how deep each one is nested. children of
+// collapsed todos are left out unless `expand_all`
+fn flatten(todos: &[Todo], expand_all: bool) -> Vec<(usize, &Todo)> {
+    flatten_sorted(todos, expand_all, SortMode::Manual)
+}
+
+// `flatten` with the todos of each level in the sort's order, what the list shows.
+// the todos themselves stay in the manual order
+fn flatten_sorted(todos: &[Todo], expand_all: bool, mode: SortMode) -> Vec<(usize, &Todo)> {
+    fn walk<'a>(
+        todos: &'a [Todo],
+        depth: usize,
+        expand_all: bool,
+        mode: SortMode,
+        rows: &mut Vec<(usize, &'a Todo)>,
+    ) {
+        let mut level: Vec<&Todo> = todos.iter().collect();
+        sort_level(&mut level, mode);
+        for todo in level {
+            rows.push((depth, todo));
+            if expand_all || !todo.collapsed {
+                walk(&todo.children, depth + 1, expand_all, mode, rows);
+            }
+        }
+    }
+    let mut rows = Vec::new();
+    walk(todos, 0, expand_all, mode, &mut rows);
+    rows
+}
+
+// the list holding the todo with this id, at any depth, and its index in that list
+fn containing_list(todos: &mut Vec<Todo>, id: u64) -> Option<(&mut Vec<Todo>, usize)> {
+    if let Some(position) = todos.iter().position(|todo| todo.id == id) {
+        return Some((todos, position));
+    }
+    todos
+        .iter_mut()
+        .find_map(|todo| containing_list(&mut todo.children, id))
+}
+
+fn find_todo(todos: &[Todo], id: u64) -> Option<&Todo> {
+    todos.iter().find_map(|todo| {
+        if todo.id == id {
+            Some(todo)
+        } else {
+            find_todo(&todo.children, id)
+        }
+    })
+}
+
+fn find_todo_mut(todos: &mut Vec<Todo>, id: u64) -> Option<&mut Todo> {
+    containing_list(todos, id).map(|(list, position)| &mut list[position])
+}
+
+// takes the todo out of whichever list holds it, with its index in that list
+fn remove_todo(todos: &mut Vec<Todo>, id: u64) -> Option<(Todo, usize)> {
+    containing_list(todos, id).map(|(list, position)| (list.remove(position), position))
+}
+
+// the done todos, subtasks of todos that aren't done included, taken out of
+// the tree. a done todo takes its subtasks along with it
+fn take_done(todos: &mut Vec<Todo>) -> Vec<Todo> {
+    let (mut done, mut active): (Vec<Todo>, Vec<Todo>) =
+        mem::take(todos).into_iter().partition(|todo| todo.done);
+    for todo in &mut active {
+        done.extend(take_done(&mut todo.children));
+    }
+    *todos = active;
+    done
+}
+
+// files written before ids existed have every id set to 0
+fn assign_missing_ids(todos: &mut [Todo], next_id: &mut u64) {
+    for todo in todos {
+        if todo.id == 0 {
+            todo.id = *next_id;
+            *next_id += 1;
+        }
+        assign_missing_ids(&mut todo.children, next_id);
+    }
+}
+
+// sorts every level of the tree, subtasks stay under their parent
+fn sort_tree(todos: &mut [Todo], mode: SortMode) {
+    sort_level(todos, mode);
+    for todo in todos {
+        sort_tree(&mut todo.children, mode);
+    }
+}
+
+// sorts one level, the todos themselves or references to them
+fn sort_level<T: Borrow<Todo>>(todos: &mut [T], mode: SortMode) {
+    // sort_by_key is stable, so ties keep their current order
+    match mode {
+        SortMode::Alphabetical => todos.sort_by_key(|todo| todo.borrow().text.to_lowercase()),
+        SortMode::CreatedAt => todos.sort_by_key(|todo| todo.borrow().id),
+        // highest priority first
+        SortMode::Priority => todos.sort_by_key(|todo| Reverse(todo.borrow().priority)),
+        SortMode::Completed => todos.sort_by_key(|todo| todo.borrow().done),
+        SortMode::Manual => {}
+    }
+}
+
+// splits "buy milk #shopping #urgent" into ("buy milk", ["shopping", "urgent"]).
+// splitting on whitespace also trims the text and collapses runs of spaces/tabs
+// into single spaces, so "  " comes back empty
+fn parse_tags(input: &str) -> (String, Vec<String>) {
+    let mut words = Vec::new();
+    let mut tags: Vec<String> = Vec::new();
+    for word in input.split_whitespace() {
+        match word.strip_prefix('#') {
+            Some(tag) if !tag.is_empty() => {
+                if !tags.iter().any(|t| t == tag) {
+                    tags.push(tag.to_string());
+                }
+            }
+            _ => words.push(word),
+        }
+    }
+    (words.join(" "), tags)
+}
+
+#[derive(Default)]
+pub struct App {
+    pub input_mode: InputMode,
+    pub search_input: String,
+    pub todos: Vec<Todo>,
+    // ids of the todos shown, in display order. they're looked up in the view's
+    // list with `shown_todos` and `selected_todo`
+    pub filtered_todos: Vec<u64>,
+    // how deeply nested each of `filtered_todos` is, for indenting subtasks
+    pub row_depths: Vec<usize>,
+    pub selected_index: Option<usize>,
+    // scroll offset of the rendered list, kept between frames
+    pub list_state: ListState,
+    // for add
+    pub input_buffer: String,
+    // what the input started as, Esc asks before throwing away anything else
+    pub original_input: String,
+    // the mode to go back to when the discard is cancelled
+    pub discard_from: InputMode,
+    // the todo a new subtask goes under, `None` when adding a top level todo
+    pub adding_parent: Option<u64>,
+    // cursor position in `input_buffer`, counted in chars (not bytes)
+    pub cursor_pos: usize,
+    pub show_confirmation: bool,
+    // where the todos are saved, `None` if the file couldn't be loaded so we don't overwrite it
+    pub data_path: Option<PathBuf>,
+    // shown in the status bar for a few seconds
+    pub message: Option<Message>,
+    // id handed out to the next todo, ids start at 1
+    pub next_id: u64,
+    pub sort_mode: SortMode,
+    // snapshots of the lists taken before each change
+    pub undo_stack: Vec<Snapshot>,
+    pub redo_stack: Vec<Snapshot>,
+    // set when `todos` changed and the change hasn't made it to disk yet
+    pub dirty: bool,
+    // the latest save failed, the app exits with an error code if it stays that way
+    pub save_failed: bool,
+    // when the latest unsaved change happened, for the autosave debounce
+    pub last_change: Option<Instant>,
+    // first half of a `gg` motion
+    pub pending_g: bool,
+    // digits typed before a motion, the 12 in `12G`
+    pub pending_count: Option<usize>,
+    // where the todo list (border included) was drawn last frame, used for paging
+    // and to map mouse clicks to todos
+    pub list_area: Rect,
+    // rows each shown todo took on the last draw, long ones wrap over several
+    pub item_heights: Vec<usize>,
+    // cut long todos with "…" instead of wrapping them, from config.toml
+    pub truncate_todos: bool,
+    // typing stops here while adding or editing, from config.toml
+    pub max_len: Option<usize>,
+    // when and on which todo the last left click landed, to spot double-clicks
+    pub last_click: Option<(Instant, usize)>,
+    // searches ignore case unless this is toggled on
+    pub case_sensitive: bool,
+    // only match whole words, "cat" doesn't find "concatenate"
+    pub whole_word: bool,
+    pub search_mode: SearchMode,
+    pub status_filter: StatusFilter,
+    pub age_filter: AgeFilter,
+    // split the list under Today/Yesterday/Earlier headers, off for a flat list
+    pub group_by_date: bool,
+    // the group of each of `filtered_todos`, filled while grouping
+    pub row_groups: Vec<DateGroup>,
+    // the selected todo's details and notes beside the list
+    pub show_preview: bool,
+    // how much of the width the preview gets, in percent. from config.toml
+    pub preview_width: Option<u16>,
+    pub keymap: Keymap,
+    pub theme: Theme,
+    pub symbols: Symbols,
+    // first line of the help popup that's on screen
+    pub help_scroll: u16,
+    // ids of the todos marked with `v`, for bulk deletes
+    pub marked: HashSet<u64>,
+    // completed todos moved out of the way with `X`
+    pub archived: Vec<Todo>,
+    pub trash: Vec<Todo>,
+    // where each trashed todo was in `todos`, so restoring can put it back there
+    pub trash_positions: HashMap<u64, usize>,
+    pub view: View,
+    // every list, shown as tabs. the active one's todos are in `todos`. empty until
+    // a second list is created, which means a single list called "Todos"
+    pub lists: Vec<TodoList>,
+    pub active_list: usize,
+    // the open database when saving to todos.db
+    pub store: Option<sqlite::Store>,
+    // set by `:q`, the event loop exits once it sees it
+    pub should_quit: bool,
+    pub clipboard: Clipboard,
+}
+
+impl App {
+    pub fn load(path: &Path) -> Result<App, AppError> {
+        let (mut data, mut archived, store) = match Format::from_path(path) {
+            Format::Sqlite => {
+                let mut store = sqlite::Store::open(path)?;
+                match store.load()? {
+                    Some((state, mut places)) => {
+                        let mut data: DataFile = serde_json::from_str(&state)?;
+                        for (index, list) in data.lists.iter_mut().enumerate() {
+                            list.todos = places.remove(&list_place(index)).unwrap_or_default();
+                        }
+                        let archived = places.remove(ARCHIVE_PLACE).unwrap_or_default();
+                        (data, archived, Some(store))
+                    }
+                    // a new database, start from todos.json if there is one. it's copied
+                    // over by the save at the end
+                    None => {
+                        let json_path = path.with_extension("json");
+                        let data = read_data_file(&json_path)?;
+                        let archived = read_archive(&json_path)?;
+                        (data, archived, Some(store))
+                    }
+                }
+            }
+            _ => (read_data_file(path)?, read_archive(path)?, None),
+        };
+        let mut lists = mem::take(&mut data.lists);
+        if lists.is_empty() {
+            lists.push(TodoList {
+                name: DEFAULT_LIST_NAME.to_string(),
+                todos: data.todos,
+            });
+        }
+
+        // ids are unique across all the lists
+        let mut next_id = lists
+            .iter()
+            .flat_map(|list| flatten(&list.todos, true))
+            .chain(flatten(&archived, true))
+            .map(|(_, todo)| todo.id)
+            .max()
+            .unwrap_or(0)
+            + 1;
+        for list in &mut lists {
+            assign_missing_ids(&mut list.todos, &mut next_id);
+        }
+        assign_missing_ids(&mut archived, &mut next_id);
+
+        let active_list = data.active_list.min(lists.len() - 1);
+        let todos = mem::take(&mut lists[active_list].todos);
+        let mut app = Self {
+            todos,
+            lists,
+            active_list,
+            archived,
+            data_path: Some(path.to_path_buf()),
+            next_id,
+            list_state: ListState::default().with_offset(data.scroll_offset),
+            selected_index: data.selected_index,
+            store,
+            ..Default::default()
+        };
+        // filtering clamps the saved index into range
+        app.filter_todos();
+        if let Some(id) = data.selected_id {
+            app.select_id(id);
+        }
+        if app.store.is_some() {
+            app.save(path)?;
+        }
+        Ok(app)
+    }
+
+    // one todo per non-blank line, for `todo-app-v1 tasks.txt` and piped input. there's
+    // no data file, so it's a session of its own that leaves the saved todos alone
+    pub fn from_lines(lines: impl Iterator<Item = String>) -> App {
+        let mut app = App::default();
+        for line in lines {
+            let (text, tags) = parse_tags(&line);
+            if text.is_empty() {
+                continue;
+            }
+            let mut todo = app.new_todo(text);
+            todo.tags = tags;
+            app.todos.push(todo);
+        }
+        app.filter_todos();
+        app
+    }
+
+    pub fn save(&mut self, path: &Path) -> Result<(), AppError> {
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        let mut lists = self.lists.clone();
+        if lists.is_empty() {
+            lists.push(TodoList::new(DEFAULT_LIST_NAME));
+        }
+        if let Some(active) = lists.get_mut(self.active_list) {
+            active.todos = self.todos.clone();
+        }
+        let data = DataFile {
+            todos: Vec::new(),
+            lists,
+            active_list: self.active_list,
+            selected_id: self.selected_id(),
+            selected_index: self.selected_index,
+            scroll_offset: self.list_state.offset(),
+        };
+        let format = Format::from_path(path);
+        if format == Format::Sqlite {
+            return self.save_to_store(path, data);
+        }
+        fs::write(path, format.serialize(&data)?)?;
+        let archive = match format {
+            Format::Toml => format.serialize(&TodosFile {
+                todos: self.archived.clone(),
+            })?,
+            _ => format.serialize(&self.archived)?,
+        };
+        fs::write(archive_path(path), archive)?;
+        Ok(())
+    }
+
+    // the todos go in rows of their own, the state row keeps the rest of `data`
+    fn save_to_store(&mut self, path: &Path, mut data: DataFile) -> Result<(), AppError> {
+        if self.store.as_ref().is_none_or(|store| store.path() != path) {
+            self.store = Some(sqlite::Store::open(path)?);
+        }
+        let lists: Vec<Vec<Todo>> = data
+            .lists
+            .iter_mut()
+            .map(|list| mem::take(&mut list.todos))
+            .collect();
+        let state = serde_json::to_string(&data).map_err(AppError::Serialize)?;
+        let places: Vec<(String, &[Todo])> = lists
+            .iter()
+            .enumerate()
+            .map(|(index, todos)| (list_place(index), todos.as_slice()))
+            .chain([(ARCHIVE_PLACE.to_string(), self.archived.as_slice())])
+            .collect();
+        match &mut self.store {
+            Some(store) => store.save(&state, &places),
+            None => Ok(()),
+        }
+    }
+
+    // called after every change to `todos`, the next maybe_autosave writes it out
+    fn persist(&mut self) {
+        self.dirty = true;
+        self.last_change = Some(Instant::now());
+    }
+
+    // called every tick, saves once the changes have settled so a burst of edits
+    // is a single write
+    pub fn maybe_autosave(&mut self, now: Instant) {
+        let Some(last_change) = self.last_change else {
+            return;
+        };
+        if self.data_path.is_some() && now.duration_since(last_change) >= AUTOSAVE_DELAY {
+            // a failed save shows its error and isn't retried until the next change
+            self.last_change = None;
+            self.save_to_disk();
+        }
+    }
+
+    pub fn set_message(&mut self, text: &str) {
+        self.show_message(text, Severity::Info);
+    }
+
+    pub fn set_error(&mut self, text: &str) {
+        self.show_message(text, Severity::Error);
+    }
+
+    fn show_message(&mut self, text: &str, severity: Severity) {
+        self.message = Some(Message {
+            text: text.to_string(),
+            severity,
+            shown_at: Instant::now(),
+        });
+    }
+
+    // called every tick, drops the message once it's been up long enough
+    pub fn expire_message(&mut self, now: Instant) {
+        if self
+            .message
+            .as_ref()
+            .is_some_and(|message| now.duration_since(message.shown_at) >= MESSAGE_DURATION)
+        {
+            self.message = None;
+        }
+    }
+
+    // returns whether the todos made it to disk
+    pub fn save_to_disk(&mut self) -> bool {
+        let result = match self.data_path.clone() {
+            Some(path) => self.save(&path),
+            None => Err(AppError::NoDataFile),
+        };
+        match result {
+            Ok(()) => {
+                self.dirty = false;
+                self.save_failed = false;
+                true
+            }
+            Err(err) => {
+                self.set_error(&format!("Failed to save todos: {}", err));
+                self.save_failed = true;
+                false
+            }
+        }
+    }
+
+    // the final save on the way out, also so the selection is remembered. false
+    // when there are changes that couldn't be saved
+    pub fn save_on_quit(&mut self) -> bool {
+        match self.data_path {
+            Some(_) => self.save_to_disk(),
+            None => !self.dirty,
+        }
+    }
+
+    fn new_todo(&mut self, text: String) -> Todo {
+        let id = self.take_id();
+        Todo::new(id, text)
+    }
+
+    fn take_id(&mut self) -> u64 {
+        self.next_id = self.next_id.max(1);
+        self.next_id += 1;
+        self.next_id - 1
+    }
+
+    // gives a todo from elsewhere (and its subtasks) ids that are free in this app
+    fn assign_new_ids(&mut self, todo: &mut Todo) {
+        todo.id = self.take_id();
+        for child in &mut todo.children {
+            self.assign_new_ids(child);
+        }
+    }
+
+    // the selected item in `todos`, resolved by id so duplicates can't be confused
+    fn selected_todo_mut(&mut self) -> Option<&mut Todo> {
+        let id = self.selected_id()?;
+        find_todo_mut(&mut self.todos, id)
+    }
+
+    pub fn selected_id(&self) -> Option<u64> {
+        self.selected_index
+            .and_then(|i| self.filtered_todos.get(i))
+            .copied()
+    }
+
+    pub fn selected_todo(&self) -> Option<&Todo> {
+        find_todo(self.view_todos(), self.selected_id()?)
+    }
+
+    // the shown todos in display order, what `filtered_todos` points at
+    pub fn shown_todos(&self) -> Vec<&Todo> {
+        let by_id: HashMap<u64, &Todo> = flatten(self.view_todos(), true)
+            .into_iter()
+            .map(|(_, todo)| (todo.id, todo))
+            .collect();
+        self.filtered_todos
+            .iter()
+            .filter_map(|id| by_id.get(id).copied())
+            .collect()
+    }
+
+    // the list the current view shows
+    fn view_todos(&self) -> &[Todo] {
+        match self.view {
+            View::Active => &self.todos,
+            View::Archived => &self.archived,
+            View::Trash => &self.trash,
+        }
+    }
+
+    // point the selection at the todo with this id, if it's visible
+    pub fn select_id(&mut self, id: u64) {
+        if let Some(index) = self.filtered_todos.iter().position(|&shown| shown == id) {
+            self.selected_index = Some(index);
+        }
+    }
+
+    fn current_lists(&self) -> Snapshot {
+        Snapshot {
+            todos: self.todos.clone(),
+            archived: self.archived.clone(),
+            trash: self.trash.clone(),
+        }
+    }
+
+    // swaps the lists for `snapshot`, returning what they were
+    fn replace_lists(&mut self, snapshot: Snapshot) -> Snapshot {
+        Snapshot {
+            todos: mem::replace(&mut self.todos, snapshot.todos),
+            archived: mem::replace(&mut self.archived, snapshot.archived),
+            trash: mem::replace(&mut self.trash, snapshot.trash),
+        }
+    }
+
+    // call before changing the lists so the change can be undone
+    fn snapshot(&mut self) {
+        let lists = self.current_lists();
+        self.push_undo(lists);
+    }
+
+    fn push_undo(&mut self, lists: Snapshot) {
+        self.undo_stack.push(lists);
+        if self.undo_stack.len() > MAX_UNDO_HISTORY {
+            self.undo_stack.remove(0);
+        }
+        // a new change makes the undone ones unreachable
+        self.redo_stack.clear();
+    }
+
+    pub fn undo(&mut self) {
+        if let Some(snapshot) = self.undo_stack.pop() {
+            let current = self.replace_lists(snapshot);
+            self.redo_stack.push(current);
+            self.restore_history_step();
+        }
+    }
+
+    pub fn redo(&mut self) {
+        if let Some(snapshot) = self.redo_stack.pop() {
+            let current = self.replace_lists(snapshot);
+            self.undo_stack.push(current);
+            self.restore_history_step();
+        }
+    }
+
+    fn restore_history_step(&mut self) {
+        let selected_id = self.selected_id();
+        self.filter_todos();
+        if let Some(id) = selected_id {
+            self.select_id(id);
+        }
+        self.persist();
+    }
+
+    pub fn move_selection_up(&mut self) {
+        if self.filtered_todos.is_empty() {
+            // nothing to select
+            self.selected_index = None;
+            return;
+        }
+
+        self.selected_index = match self.selected_index {
+            Some(i) => {
+                if i > 0 {
+                    Some(i - 1)
+                } else {
+                    // go to the bottom
+                    Some(self.filtered_todos.len() - 1)
+                }
+            }
+            None => Some(0),
+        };
+    }
+
+    pub fn move_selection_down(&mut self) {
+        let len = self.filtered_todos.len();
+        if len == 0 {
+            // nothing to select
+            self.selected_index = None;
+            return;
+        }
+
+        self.selected_index = match self.selected_index {
+            Some(i) => {
+                if i < len - 1 {
+                    Some(i + 1)
+                } else {
+                    // to go the start
+                    Some(0)
+                }
+            }
+            None => Some(0),
+        }
+    }
+
+    pub fn select_first(&mut self) {
+        self.selected_index = if self.filtered_todos.is_empty() {
+            None
+        } else {
+            Some(0)
+        };
+    }
+
+    // selects the todo with this (1-based) line number, or the last one if it's too big
+    pub fn jump_to(&mut self, number: usize) {
+        let Some(last) = self.filtered_todos.len().checked_sub(1) else {
+            self.selected_index = None;
+            return;
+        };
+        self.selected_index = Some(number.saturating_sub(1).min(last));
+    }
+
+    pub fn select_last(&mut self) {
+        self.selected_index = self.filtered_todos.len().checked_sub(1);
+    }
+
+    // n, wraps around to the top. stays put when everything is done
+    pub fn select_next_incomplete(&mut self) {
+        let len = self.filtered_todos.len();
+        let start = self.selected_index.map_or(0, |i| i + 1);
+        self.select_incomplete((0..len).map(|step| (start + step) % len));
+    }
+
+    // N, wraps around to the bottom
+    pub fn select_prev_incomplete(&mut self) {
+        let len = self.filtered_todos.len();
+        let start = self.selected_index.unwrap_or(0) + len;
+        self.select_incomplete((1..=len).map(|step| (start - step) % len));
+    }
+
+    fn select_incomplete(&mut self, mut indexes: impl Iterator<Item = usize>) {
+        let shown = self.shown_todos();
+        if let Some(index) = indexes.find(|&i| !shown[i].done) {
+            self.selected_index = Some(index);
+        }
+    }
+
+    // rows of todos that fit inside the list border
+    fn list_height(&self) -> usize {
+        self.list_area.height.saturating_sub(2) as usize
+    }
+
+    // move a screen down, stopping at the last todo
+    pub fn page_down(&mut self) {
+        let Some(last) = self.filtered_todos.len().checked_sub(1) else {
+            self.selected_index = None;
+            return;
+        };
+        let page = self.list_height().max(1);
+        self.selected_index = Some(self.selected_index.map_or(0, |i| (i + page).min(last)));
+    }
+
+    // move a screen up, stopping at the first todo
+    pub fn page_up(&mut self) {
+        if self.filtered_todos.is_empty() {
+            self.selected_index = None;
+            return;
+        }
+        let page = self.list_height().max(1);
+        self.selected_index = Some(self.selected_index.map_or(0, |i| i.saturating_sub(page)));
+    }
+
+    // e.g. "5 todos, 2 done", for the status bar
+    pub fn summary(&self) -> String {
+        let (done, total) = self.completion();
+        format!("{} todos, {} done", total, done)
+    }
+
+    // (done, total) over the whole list, subtasks included
+    pub fn completion(&self) -> (usize, usize) {
+        let all = flatten(&self.todos, true);
+        let done = all.iter().filter(|(_, todo)| todo.done).count();
+        (done, all.len())
+    }
+
+    // printed after quitting, counts every list
+    pub fn exit_summary(&self) -> String {
+        let mut active = 0;
+        let mut completed = 0;
+        let other_lists = self.lists.iter().map(|list| &list.todos);
+        for todos in [&self.todos].into_iter().chain(other_lists) {
+            for (_, todo) in flatten(todos, true) {
+                if todo.done {
+                    completed += 1;
+                } else {
+                    active += 1;
+                }
+            }
+        }
+        format!("{} active, {} completed", active, completed)
+    }
+
+    pub fn handle_mouse(&mut self, mouse: MouseEvent) {
+        if !matches!(self.input_mode, InputMode::Normal) {
+            return;
+        }
+        match mouse.kind {
+            MouseEventKind::Down(MouseButton::Left) => {
+                self.click(mouse.column, mouse.row, Instant::now())
+            }
+            MouseEventKind::ScrollDown => self.scroll_down(),
+            MouseEventKind::ScrollUp => self.scroll_up(),
+            _ => {}
+        }
+    }
+
+    // the todo drawn at this screen position, if any
+    fn todo_index_at(&self, column: u16, row: u16) -> Option<usize> {
+        let area = self.list_area;
+        // the border takes one cell on every side
+        let inside = column > area.x
+            && column + 1 < area.x + area.width
+            && row > area.y
+            && row + 1 < area.y + area.height;
+        if !inside {
+            return None;
+        }
+        // walk down from the first todo on screen, they can span several rows
+        let mut rows_left = (row - area.y - 1) as usize;
+        for index in self.list_state.offset()..self.filtered_todos.len() {
+            let height = self.item_heights.get(index).copied().unwrap_or(1);
+            if rows_left < height {
+                return Some(index);
+            }
+            rows_left -= height;
+        }
+        None
+    }
+
+    // selects the clicked todo, a second click on it shortly after starts editing it
+    pub fn click(&mut self, column: u16, row: u16, now: Instant) {
+        let Some(index) = self.todo_index_at(column, row) else {
+            self.last_click = None;
+            return;
+        };
+        let double_click = self.last_click.is_some_and(|(at, last_index)| {
+            last_index == index && now.duration_since(at) <= DOUBLE_CLICK_INTERVAL
+        });
+        self.selected_index = Some(index);
+        if double_click {
+            self.last_click = None;
+            self.start_editing();
+        } else {
+            self.last_click = Some((now, index));
+        }
+    }
+
+    // the wheel moves the view a todo at a time. the selection stays put unless it
+    // would scroll out of sight, then it's dragged along with the edge
+    pub fn scroll_down(&mut self) {
+        let offset = (self.list_state.offset() + 1).min(self.max_scroll_offset());
+        *self.list_state.offset_mut() = offset;
+        self.keep_selection_in_view();
+    }
+
+    pub fn scroll_up(&mut self) {
+        let offset = self.list_state.offset().saturating_sub(1);
+        *self.list_state.offset_mut() = offset;
+        self.keep_selection_in_view();
+    }
+
+    fn item_height(&self, index: usize) -> usize {
+        self.item_heights.get(index).copied().unwrap_or(1)
+    }
+
+    // the first todo that still fills the list down to the last one, scrolling further
+    // would only leave empty rows at the bottom
+    fn max_scroll_offset(&self) -> usize {
+        let mut rows = 0;
+        for index in (0..self.filtered_todos.len()).rev() {
+            rows += self.item_height(index);
+            if rows > self.list_height() {
+                return (index + 1).min(self.filtered_todos.len() - 1);
+            }
+        }
+        0
+    }
+
+    fn keep_selection_in_view(&mut self) {
+        let Some(selected) = self.selected_index else {
+            return;
+        };
+        let offset = self.list_state.offset();
+        // the last todo that fits completely from the offset down
+        let mut last = offset;
+        let mut rows = self.item_height(offset);
+        while last + 1 < self.filtered_todos.len()
+            && rows + self.item_height(last + 1) <= self.list_height()
+        {
+            last += 1;
+            rows += self.item_height(last);
+        }
+        self.selected_index = Some(selected.clamp(offset, last));
+    }
+
+    pub fn toggle_theme(&mut self) {
+        self.theme = self.theme.toggled();
+    }
+
+    pub fn toggle_whole_word(&mut self) {
+        self.whole_word = !self.whole_word;
+        self.filter_todos();
+    }
+
+    pub fn toggle_case_sensitive(&mut self) {
+        self.case_sensitive = !self.case_sensitive;
+        self.filter_todos();
+    }
+
+    // the cursor picks up at the end of whatever was searched for last
+    pub fn start_search(&mut self) {
+        self.cursor_pos = self.search_input.chars().count();
+        self.input_mode = InputMode::Searching;
+    }
+
+    // `t`, a search that starts out as `#` so whatever is typed is a tag
+    pub fn start_tag_search(&mut self) {
+        self.search_input = "#".to_string();
+        self.start_search();
+        self.filter_todos();
+    }
+
+    // the options go back to their defaults along with the text
+    pub fn clear_search(&mut self) {
+        self.search_input.clear();
+        self.cursor_pos = 0;
+        self.case_sensitive = false;
+        self.whole_word = false;
+        self.filter_todos();
+    }
+
+    pub fn cycle_status_filter(&mut self) {
+        self.status_filter = self.status_filter.next();
+        self.filter_todos();
+    }
+
+    pub fn cycle_age_filter(&mut self) {
+        self.age_filter = self.age_filter.next();
+        self.filter_todos();
+    }
+
+    pub fn toggle_group_by_date(&mut self) {
+        self.group_by_date = !self.group_by_date;
+        self.filter_todos();
+    }
+
+    // the header drawn above the shown todo at `index`, where a new date group starts
+    pub fn group_header(&self, index: usize) -> Option<&'static str> {
+        let group = self.row_groups.get(index)?;
+        if index > 0 && self.row_groups.get(index - 1) == Some(group) {
+            return None;
+        }
+        Some(group.label())
+    }
+
+    // any filter besides the view itself is on
+    pub fn is_filtered(&self) -> bool {
+        !self.search_input.is_empty()
+            || self.status_filter != StatusFilter::All
+            || self.age_filter != AgeFilter::All
+    }
+
+    pub fn toggle_search_mode(&mut self) {
+        self.search_mode = self.search_mode.next();
+        self.filter_todos();
+    }
+
+    pub fn filter_todos(&mut self) {
+        // narrowing a search shouldn't move the selection to another todo
+        let previous_id = self.selected_id();
+        let source = match self.view {
+            View::Active => &self.todos,
+            View::Archived => &self.archived,
+            View::Trash => &self.trash,
+        };
+        let today = Local::now().date_naive();
+        // searches look inside collapsed todos too
+        let rows: Vec<(usize, &Todo)> =
+            flatten_sorted(source, !self.search_input.is_empty(), self.sort_mode)
+                .into_iter()
+                .filter(|(_, todo)| {
+                    self.status_filter.matches(todo) && self.age_filter.matches(todo, today)
+                })
+                .collect();
+        let rows: Vec<(usize, &Todo)> = if self.search_input.is_empty() {
+            rows
+        } else if let Some(tag) = self.search_input.strip_prefix('#') {
+            // `#tag` searches match tags exactly instead of the text, a lone `#` shows
+            // everything that has a tag
+            rows.into_iter()
+                .filter(|(_, todo)| {
+                    todo.tags.iter().any(|t| {
+                        if tag.is_empty() {
+                            true
+                        } else if self.case_sensitive {
+                            t == tag
+                        } else {
+                            t.to_lowercase() == tag.to_lowercase()
+                        }
+                    })
+                })
+                .collect()
+        } else {
+            match self.search_mode {
+                SearchMode::Substring => rows
+                    .into_iter()
+                    .filter(|(_, todo)| {
+                        !match_ranges(
+                            &todo.text,
+                            &self.search_input,
+                            self.case_sensitive,
+                            self.whole_word,
+                        )
+                        .is_empty()
+                    })
+                    .collect(),
+                SearchMode::Fuzzy => {
+                    let mut matches: Vec<(i64, (usize, &Todo))> = rows
+                        .into_iter()
+                        .filter_map(|row| {
+                            fuzzy_match(&row.1.text, &self.search_input, self.case_sensitive)
+                                .map(|(score, _)| (score, row))
+                        })
+                        .collect();
+                    // best match first, equal scores keep the list order
+                    matches.sort_by_key(|(score, _)| Reverse(*score));
+                    matches.into_iter().map(|(_, row)| row).collect()
+                }
+            }
+        };
+        // subtasks go with their top level todo, the sort is stable so each group
+        // keeps the sort order
+        let mut rows: Vec<(DateGroup, (usize, &Todo))> = rows
+            .into_iter()
+            .scan(None, |top_level_group, (depth, todo)| {
+                let group = DateGroup::of(todo, today);
+                if depth == 0 || top_level_group.is_none() {
+                    *top_level_group = Some(group);
+                }
+                Some((top_level_group.unwrap_or(group), (depth, todo)))
+            })
+            .collect();
+        if self.group_by_date {
+            rows.sort_by_key(|(group, _)| *group);
+            self.row_groups = rows.iter().map(|(group, _)| *group).collect();
+        } else {
+            self.row_groups.clear();
+        }
+        let rows: Vec<(usize, &Todo)> = rows.into_iter().map(|(_, row)| row).collect();
+        self.row_depths = rows.iter().map(|(depth, _)| *depth).collect();
+        self.filtered_todos = rows.into_iter().map(|(_, todo)| todo.id).collect();
+
+        // stay on the same todo if it's still shown, otherwise keep the index in bounds
+        let len = self.filtered_todos.len();
+        let previous_index =
+            previous_id.and_then(|id| self.filtered_todos.iter().position(|&shown| shown == id));
+        self.selected_index = match previous_index.or(self.selected_index) {
+            // an empty list can't have a selection
+            _ if len == 0 => None,
+            Some(selected) if selected >= len => Some(len - 1),
+            Some(selected) => Some(selected),
+            // the list was empty before, select the first match again
+            None => Some(0),
+        };
+
+        // marks on todos that aren't shown anymore would get deleted unseen
+        let shown: HashSet<u64> = self.filtered_todos.iter().copied().collect();
+        self.marked.retain(|id| shown.contains(id));
+    }
+
+    // the sort only orders what's shown, the todos keep the order they were
+    // arranged in with J/K for when it's back on manual. the selection stays on
+    // the same todo
+    pub fn sort_todos(&mut self) {
+        self.filter_todos();
+    }
+
+    pub fn cycle_sort_mode(&mut self) {
+        self.sort_mode = self.sort_mode.next();
+        self.sort_todos();
+    }
+
+    // manual reordering is disabled while a search is active, the swap would
+    // happen between items that aren't next to each other on screen. subtasks
+    // move among their siblings
+    pub fn move_todo_up(&mut self) {
+        self.move_todo(|position, _| position.checked_sub(1));
+    }
+
+    pub fn move_todo_down(&mut self) {
+        self.move_todo(|position, len| Some(position + 1).filter(|&next| next < len));
+    }
+
+    // swaps the selected todo with the sibling `target` picks from (position, sibling count)
+    // only on the whole list in its own order, with todos hidden or grouped the
+    // neighbour on screen isn't the one it would swap with
+    fn move_todo(&mut self, target: impl Fn(usize, usize) -> Option<usize>) {
+        if self.is_filtered() || self.group_by_date {
+            return;
+        }
+        let Some(id) = self.selected_id() else {
+            return;
+        };
+        // moving starts from the order on screen, which only becomes the manual
+        // one if the todo actually moves. the undo step brings back the old order
+        let mut todos = self.todos.clone();
+        sort_tree(&mut todos, self.sort_mode);
+        let Some((siblings, position)) = containing_list(&mut todos, id) else {
+            return;
+        };
+        if let Some(other) = target(position, siblings.len()) {
+            siblings.swap(position, other);
+            let before = self.current_lists();
+            self.todos = todos;
+            self.push_undo(before);
+            // the list isn't in the sort's order anymore
+            self.sort_mode = SortMode::Manual;
+            self.filter_todos();
+            self.select_id(id);
+            self.persist();
+        }
+    }
+
+    pub fn add_todo(&mut self) {
+        let (text, tags) = parse_tags(&self.input_buffer);
+        if !text.is_empty() {
+            self.snapshot();
+            let mut todo = self.new_todo(text);
+            todo.tags = tags;
+            match self
+                .adding_parent
+                .take()
+                .and_then(|id| find_todo_mut(&mut self.todos, id))
+            {
+                Some(parent) => {
+                    // show the new subtask
+                    parent.collapsed = false;
+                    parent.children.push(todo);
+                }
+                None => self.todos.push(todo),
+            }
+            self.input_buffer.clear();
+            self.cursor_pos = 0;
+            self.filter_todos(); // refresh filtered list
+            self.persist();
+        }
+    }
+
+    // `--import todo.txt`: one todo per line, blank lines skipped and the ones already
+    // in the list left out. a file that isn't UTF-8 is an error, not a panic
+    pub fn import_lines(&mut self, path: &Path) -> Result<(usize, usize), AppError> {
+        let contents = fs::read_to_string(path)?;
+        Ok(self.merge_todos(todos_from_lines(&contents)))
+    }
+
+    // adds the imported todos after the current ones, leaving out the ones whose text
+    // is already in the list. returns how many were added and skipped
+    pub fn merge_todos(&mut self, imported: Vec<Todo>) -> (usize, usize) {
+        let mut texts: HashSet<String> = flatten(&self.todos, true)
+            .into_iter()
+            .map(|(_, todo)| todo.text.clone())
+            .collect();
+        let before = self.current_lists();
+        let (mut added, mut skipped) = (0, 0);
+        for mut todo in imported {
+            if !texts.insert(todo.text.clone()) {
+                skipped += 1;
+                continue;
+            }
+            self.assign_new_ids(&mut todo);
+            self.todos.push(todo);
+            added += 1;
+        }
+        if added > 0 {
+            self.push_undo(before);
+            self.filter_todos();
+            self.persist();
+        }
+        self.set_message(&format!(
+            "Imported {} todos, skipped {} already in the list",
+            added, skipped
+        ));
+        (added, skipped)
+    }
+
+    pub fn delete_selected_todo(&mut self) {
+        let (Some(selected_index), Some(id)) = (self.selected_index, self.selected_id()) else {
+            return;
+        };
+        let before = self.current_lists();
+        // subtasks go along with their parent
+        if let Some((todo, position)) = remove_todo(&mut self.todos, id) {
+            self.push_undo(before);
+            self.set_message(&format!("Deleted '{}' (u to undo)", todo.text));
+            self.move_to_trash(todo, position);
+            self.filter_todos(); // refresh filtered list
+            self.persist();
+
+            // adjust selection
+            if self.filtered_todos.is_empty() {
+                self.selected_index = None
+            } else {
+                self.selected_index = Some(selected_index.min(self.filtered_todos.len() - 1))
+            }
+        }
+    }
+
+    pub fn start_clear_all_confirmation(&mut self) {
+        if self.view == View::Active && !self.todos.is_empty() {
+            self.input_mode = InputMode::ClearAllConfirm;
+        }
+    }
+
+    // everything goes to the trash in one undoable step
+    pub fn clear_all(&mut self) {
+        if self.todos.is_empty() {
+            return;
+        }
+        self.snapshot();
+        self.set_message(&format!(
+            "Moved {} todos to the trash (u to undo)",
+            self.todos.len()
+        ));
+        for (position, todo) in mem::take(&mut self.todos).into_iter().enumerate() {
+            self.move_to_trash(todo, position);
+        }
+        self.marked.clear();
+        self.filter_todos();
+        self.selected_index = None;
+        self.persist();
+    }
+
+    // restoring puts the todo back at `position` of the top level list, even if it was a subtask
+    fn move_to_trash(&mut self, todo: Todo, position: usize) {
+        self.trash_positions.insert(todo.id, position);
+        self.trash.push(todo);
+    }
+
+    pub fn start_empty_trash_confirmation(&mut self) {
+        if self.view == View::Trash && !self.trash.is_empty() {
+            self.input_mode = InputMode::EmptyTrashConfirm;
+        }
+    }
+
+    // deleting for real, though it can still be undone
+    pub fn empty_trash(&mut self) {
+        self.snapshot();
+        self.trash.clear();
+        self.trash_positions.clear();
+        self.filter_todos();
+    }
+
+    // moves every done todo to the archive
+    pub fn archive_completed(&mut self) {
+        if !flatten(&self.todos, true).iter().any(|(_, todo)| todo.done) {
+            return;
+        }
+        self.snapshot();
+        let done = take_done(&mut self.todos);
+        self.set_message(&format!("Archived {} done todos", done.len()));
+        self.archived.extend(done);
+        self.filter_todos();
+        self.persist();
+    }
+
+    // moves the selected archived todo back to the end of the todo list, or a
+    // trashed one back to where it was deleted from
+    pub fn restore_selected(&mut self) {
+        let Some(id) = self.selected_id() else {
+            return;
+        };
+        let list = match self.view {
+            View::Active => return,
+            View::Archived => &self.archived,
+            View::Trash => &self.trash,
+        };
+        let Some(position) = list.iter().position(|todo| todo.id == id) else {
+            return;
+        };
+        self.snapshot();
+        let todo = match self.view {
+            View::Trash => self.trash.remove(position),
+            _ => self.archived.remove(position),
+        };
+        let index = match self.trash_positions.remove(&id) {
+            Some(index) if self.view == View::Trash => index.min(self.todos.len()),
+            _ => self.todos.len(),
+        };
+        self.todos.insert(index, todo);
+        self.filter_todos();
+        self.persist();
+    }
+
+    // the tab names, there's always at least one
+    pub fn list_names(&self) -> Vec<&str> {
+        if self.lists.is_empty() {
+            return vec![DEFAULT_LIST_NAME];
+        }
+        self.lists.iter().map(|list| list.name.as_str()).collect()
+    }
+
+    pub fn switch_list(&mut self, index: usize) {
+        if self.lists.is_empty() {
+            self.lists.push(TodoList::new(DEFAULT_LIST_NAME));
+        }
+        if index == self.active_list || index >= self.lists.len() {
+            return;
+        }
+        // stash the current todos in their list and take out the new list's
+        mem::swap(&mut self.todos, &mut self.lists[self.active_list].todos);
+        self.active_list = index;
+        mem::swap(&mut self.todos, &mut self.lists[self.active_list].todos);
+        // the history holds the other list's todos, undoing into them would mix lists up
+        self.undo_stack.clear();
+        self.redo_stack.clear();
+        self.marked.clear();
+        self.filter_todos();
+        self.select_first();
+        self.persist();
+    }
+
+    pub fn next_list(&mut self) {
+        let count = self.list_names().len();
+        self.switch_list((self.active_list + 1) % count);
+    }
+
+    pub fn previous_list(&mut self) {
+        let count = self.list_names().len();
+        self.switch_list((self.active_list + count - 1) % count);
+    }
+
+    // switches to the list called `name`, creating it if there's none
+    pub fn open_list(&mut self, name: &str) {
+        if self.lists.is_empty() {
+            self.lists.push(TodoList::new(DEFAULT_LIST_NAME));
+        }
+        match self.lists.iter().position(|list| list.name == name) {
+            Some(index) => self.switch_list(index),
+            None => {
+                self.lists.push(TodoList::new(name));
+                self.switch_list(self.lists.len() - 1);
+            }
+        }
+    }
+
+    pub fn rename_list(&mut self, name: &str) {
+        if self.lists.is_empty() {
+            self.lists.push(TodoList::new(DEFAULT_LIST_NAME));
+        }
+        self.lists[self.active_list].name = name.to_string();
+        self.persist();
+    }
+
+    // only empty lists can go, so nothing gets lost by accident
+    pub fn delete_list(&mut self) {
+        if self.lists.len() < 2 {
+            self.set_error("Can't delete the only list");
+            return;
+        }
+        if !self.todos.is_empty() {
+            self.set_error("Only empty lists can be deleted");
+            return;
+        }
+        let removed = self.active_list;
+        self.switch_list(if removed == 0 { 1 } else { removed - 1 });
+        self.lists.remove(removed);
+        if self.active_list > removed {
+            self.active_list -= 1;
+        }
+    }
+
+    pub fn toggle_view(&mut self) {
+        self.view = self.view.next();
+        self.filter_todos();
+        self.select_first();
+    }
+
+    pub fn toggle_mark(&mut self) {
+        if let Some(id) = self.selected_id() {
+            if !self.marked.remove(&id) {
+                self.marked.insert(id);
+            }
+        }
+    }
+
+    // ctrl+a, e.g. to then delete or complete everything a search found
+    pub fn mark_all_shown(&mut self) {
+        self.marked.extend(self.filtered_todos.iter().copied());
+    }
+
+    pub fn delete_marked_todos(&mut self) {
+        if self.marked.is_empty() {
+            return;
+        }
+        self.snapshot();
+        let ids: Vec<u64> = self
+            .filtered_todos
+            .iter()
+            .copied()
+            .filter(|id| self.marked.contains(id))
+            .collect();
+        let mut deleted = 0;
+        for id in ids {
+            // a marked subtask of a marked parent is already gone with it
+            if let Some((todo, position)) = remove_todo(&mut self.todos, id) {
+                self.move_to_trash(todo, position);
+                deleted += 1;
+            }
+        }
+        self.set_message(&format!("Deleted {} todos (u to undo)", deleted));
+        self.marked.clear();
+        self.filter_todos();
+        self.persist();
+    }
+
+    pub fn start_delete_confirmation(&mut self) {
+        if self.selected_index.is_some() || !self.marked.is_empty() {
+            self.input_mode = InputMode::Confirming;
+            self.show_confirmation = true
+        }
+    }
+
+    pub fn cancel_delete(&mut self) {
+        self.input_mode = InputMode::Normal;
+        self.show_confirmation = false;
+    }
+
+    pub fn toggle_selected(&mut self) {
+        let before = self.current_lists();
+        if let Some(todo) = self.selected_todo_mut() {
+            let done = !todo.done;
+            todo.set_done(done);
+            self.push_undo(before);
+            self.filter_todos();
+            self.persist();
+        }
+    }
+
+    // hides or shows the subtasks of the selected todo
+    pub fn set_selected_collapsed(&mut self, collapsed: bool) {
+        let Some(id) = self.selected_id() else {
+            return;
+        };
+        if let Some(todo) = find_todo_mut(&mut self.todos, id) {
+            if todo.children.is_empty() || todo.collapsed == collapsed {
+                return;
+            }
+            todo.collapsed = collapsed;
+            self.filter_todos();
+            self.select_id(id);
+            self.persist();
+        }
+    }
+
+    pub fn start_adding_subtask(&mut self) {
+        if self.view != View::Active {
+            return;
+        }
+        if let Some(id) = self.selected_id() {
+            self.start_adding();
+            self.adding_parent = Some(id);
+        }
+    }
+
+    // marks every shown todo done, or undone if they all are already. with a search
+    // active only the matches change, so you can e.g. finish everything tagged #work
+    pub fn toggle_all(&mut self) {
+        let shown = self.filtered_todos.clone();
+        self.toggle_done(&shown);
+    }
+
+    // space with marked todos, like `toggle_all` but for the marks
+    pub fn toggle_marked(&mut self) {
+        let marked: Vec<u64> = self
+            .filtered_todos
+            .iter()
+            .copied()
+            .filter(|id| self.marked.contains(id))
+            .collect();
+        self.toggle_done(&marked);
+    }
+
+    // all of `ids` done, or undone if they all are already
+    fn toggle_done(&mut self, ids: &[u64]) {
+        if self.view != View::Active || ids.is_empty() {
+            return;
+        }
+        let done = !self
+            .shown_todos()
+            .iter()
+            .filter(|todo| ids.contains(&todo.id))
+            .all(|todo| todo.done);
+        self.snapshot();
+        for &id in ids {
+            if let Some(todo) = find_todo_mut(&mut self.todos, id) {
+                todo.set_done(done);
+            }
+        }
+        self.filter_todos();
+        self.persist();
+    }
+
+    pub fn set_selected_priority(&mut self, priority: Priority) {
+        let before = self.current_lists();
+        if let Some(todo) = self.selected_todo_mut() {
+            todo.priority = priority;
+            todo.touch();
+            self.push_undo(before);
+            self.filter_todos();
+            self.persist();
+        }
+    }
+
+    pub fn raise_selected_priority(&mut self) {
+        if let Some(priority) = self.selected_todo_mut().map(|todo| todo.priority) {
+            self.set_selected_priority(priority.higher());
+        }
+    }
+
+    pub fn lower_selected_priority(&mut self) {
+        if let Some(priority) = self.selected_todo_mut().map(|todo| todo.priority) {
+            self.set_selected_priority(priority.lower());
+        }
+    }
+
+    // the current list as Markdown checkboxes, open todos first. subtasks are nested
+    // under their parent whether or not they're done
+    pub fn markdown(&self) -> String {
+        fn write(out: &mut String, todo: &Todo, depth: usize) {
+            let checkbox = if todo.done { "[x]" } else { "[ ]" };
+            let tags: String = todo.tags.iter().map(|tag| format!(" #{}", tag)).collect();
+            out.push_str(&format!(
+                "{}- {} **{}** {}{}\n",
+                "  ".repeat(depth),
+                checkbox,
+                todo.priority.label(),
+                todo.text,
+                tags
+            ));
+            for child in &todo.children {
+                write(out, child, depth + 1);
+            }
+        }
+        let names = self.list_names();
+        let name = names.get(self.active_list).unwrap_or(&DEFAULT_LIST_NAME);
+        let mut out = format!("# {}\n", name);
+        for (heading, done) in [("Active", false), ("Completed", true)] {
+            out.push_str(&format!("\n## {}\n\n", heading));
+            for todo in self.todos.iter().filter(|todo| todo.done == done) {
+                write(&mut out, todo, 0);
+            }
+        }
+        out
+    }
+
+    pub fn export_markdown(&self, path: &Path) -> io::Result<()> {
+        fs::write(path, self.markdown())
+    }
+
+    // `M`, always to todos.md next to the data file so it's easy to find
+    pub fn export_markdown_to_default(&mut self) {
+        let path = match &self.data_path {
+            Some(data_path) => data_path.with_file_name("todos.md"),
+            None => PathBuf::from("todos.md"),
+        };
+        match self.export_markdown(&path) {
+            Ok(()) => self.set_message(&format!("Exported to {}", path.display())),
+            Err(err) => self.set_error(&format!("Could not export: {}", err)),
+        }
+    }
+
+    pub fn copy_selected(&mut self) {
+        let Some(text) = self.selected_todo().map(|todo| todo.text.clone()) else {
+            return;
+        };
+        match self.clipboard.copy(&text) {
+            Ok(()) => self.set_message("Copied to the clipboard"),
+            Err(err) => self.set_error(&format!("Could not copy: {}", err)),
+        }
+    }
+
+    pub fn paste(&mut self) {
+        match self.clipboard.paste() {
+            Ok(text) => self.paste_text(&text),
+            Err(err) => self.set_error(&format!("Could not paste: {}", err)),
+        }
+    }
+
+    // inserts `text` at the cursor. pasting several lines while adding adds one todo
+    // per line, while editing they're joined into one
+    pub fn paste_text(&mut self, text: &str) {
+        let lines: Vec<&str> = text
+            .lines()
+            .filter(|line| !line.trim().is_empty())
+            .collect();
+        match (&self.input_mode, lines.as_slice()) {
+            // notes keep their line breaks
+            (InputMode::EditingNotes, _) => self.insert_str(&text.replace("\r\n", "\n")),
+            (_, []) => {}
+            (InputMode::Adding, [first, rest @ ..]) if !rest.is_empty() => {
+                let parent = self.adding_parent;
+                self.insert_str(first);
+                self.add_todo();
+                for line in rest {
+                    self.adding_parent = parent;
+                    self.input_buffer = line.to_string();
+                    self.add_todo();
+                }
+                self.input_buffer.clear();
+                self.cursor_pos = 0;
+                self.adding_parent = None;
+                self.input_mode = InputMode::Normal;
+            }
+            _ => self.insert_str(&lines.join(" ")),
+        }
+    }
+
+    pub fn start_command(&mut self) {
+        self.input_buffer.clear();
+        self.cursor_pos = 0;
+        self.input_mode = InputMode::Command;
+    }
+
+    // runs a command typed after `:`, e.g. "add buy milk" or "sort priority"
+    pub fn run_command(&mut self, command: &str) {
+        let command = command.trim();
+        let (name, argument) = command
+            .split_once(char::is_whitespace)
+            .map_or((command, ""), |(name, argument)| (name, argument.trim()));
+        match name {
+            "" => {}
+            "add" | "a" => {
+                self.input_buffer = argument.to_string();
+                self.add_todo();
+                if !self.input_buffer.is_empty() || argument.is_empty() {
+                    self.set_error("Nothing to add");
+                }
+                self.input_buffer.clear();
+                self.cursor_pos = 0;
+            }
+            "delete" | "d" => self.delete_selected_todo(),
+            "sort" => match SortMode::from_label(argument) {
+                Some(mode) => {
+                    self.sort_mode = mode;
+                    self.sort_todos();
+                }
+                None => {
+                    self.set_error(&format!("Unknown sort mode: {}", argument));
+                }
+            },
+            "list" if argument.is_empty() => {
+                self.set_error("Usage: list <name>");
+            }
+            "list" => self.open_list(argument),
+            "rename" if argument.is_empty() => {
+                self.set_error("Usage: rename <name>");
+            }
+            "rename" => self.rename_list(argument),
+            "dellist" => self.delete_list(),
+            "import" => match argument.split_once(char::is_whitespace) {
+                Some(("merge", path)) => match read_import(Path::new(path.trim())) {
+                    Ok(todos) => {
+                        self.merge_todos(todos);
+                    }
+                    Err(err) => self.set_error(&format!("Could not import {}: {}", path, err)),
+                },
+                _ => self.set_error("Usage: import merge <path>"),
+            },
+            "w" => {
+                if self.save_to_disk() {
+                    self.set_message("Saved");
+                }
+            }
+            // like `q` in Normal mode, asks what to do if saving fails
+            "q" | "wq" | "x" => {
+                if self.save_on_quit() {
+                    self.should_quit = true;
+                } else {
+                    self.input_mode = InputMode::QuitConfirm;
+                }
+            }
+            "q!" => self.should_quit = true,
+            _ => self.set_error(&format!("Unknown command: {}", command)),
+        }
+    }
+
+    pub fn show_help(&mut self) {
+        self.help_scroll = 0;
+        self.input_mode = InputMode::Help;
+    }
+
+    pub fn start_adding(&mut self) {
+        self.input_mode = InputMode::Adding;
+        self.adding_parent = None;
+        self.original_input.clear();
+        self.input_buffer.clear();
+        self.cursor_pos = 0;
+    }
+
+    // byte offset of the cursor, needed to edit the String in place
+    fn cursor_byte_index(&self) -> usize {
+        byte_index(&self.input_buffer, self.cursor_pos)
+    }
+
+    pub fn insert_char(&mut self, c: char) {
+        if self.at_max_len() {
+            return;
+        }
+        let index = self.cursor_byte_index();
+        self.input_buffer.insert(index, c);
+        self.cursor_pos += 1;
+    }
+
+    // only todo texts are limited, not searches, commands or notes
+    fn at_max_len(&self) -> bool {
+        matches!(self.input_mode, InputMode::Adding | InputMode::Editing)
+            && self
+                .max_len
+                .is_some_and(|max| self.input_buffer.chars().count() >= max)
+    }
+
+    pub fn insert_str(&mut self, text: &str) {
+        let index = self.cursor_byte_index();
+        self.input_buffer.insert_str(index, text);
+        self.cursor_pos += text.chars().count();
+    }
+
+    // backspace
+    pub fn delete_char_before_cursor(&mut self) {
+        if self.cursor_pos > 0 {
+            self.cursor_pos -= 1;
+            let index = self.cursor_byte_index();
+            self.input_buffer.remove(index);
+        }
+    }
+
+    // delete key
+    pub fn delete_char_at_cursor(&mut self) {
+        if self.cursor_pos < self.input_buffer.chars().count() {
+            let index = self.cursor_byte_index();
+            self.input_buffer.remove(index);
+        }
+    }
+
+    pub fn move_cursor_left(&mut self) {
+        self.cursor_pos = self.cursor_pos.saturating_sub(1);
+    }
+
+    pub fn move_cursor_right(&mut self) {
+        self.cursor_pos = (self.cursor_pos + 1).min(self.input_buffer.chars().count());
+    }
+
+    pub fn move_cursor_to_start(&mut self) {
+        self.cursor_pos = 0;
+    }
+
+    pub fn move_cursor_to_end(&mut self) {
+        self.cursor_pos = self.input_buffer.chars().count();
+    }
+
+    // ctrl+w
+    pub fn delete_word_before_cursor(&mut self) {
+        let (buffer, cursor_pos) = delete_word_before(&self.input_buffer, self.cursor_pos);
+        self.input_buffer = buffer;
+        self.cursor_pos = cursor_pos;
+    }
+
+    // ctrl+u
+    pub fn clear_input(&mut self) {
+        self.input_buffer.clear();
+        self.cursor_pos = 0;
+    }
+
+    // shared key handling for the add and edit inputs
+    pub fn handle_input_key(&mut self, code: KeyCode, modifiers: KeyModifiers) {
+        let control = modifiers.contains(KeyModifiers::CONTROL);
+        match code {
+            KeyCode::Char('w') if control => self.delete_word_before_cursor(),
+            KeyCode::Char('u') if control => self.clear_input(),
+            // other control combinations aren't text
+            KeyCode::Char(_) if control => {}
+            KeyCode::Char(c) => self.insert_char(c),
+            KeyCode::Backspace => self.delete_char_before_cursor(),
+            KeyCode::Delete => self.delete_char_at_cursor(),
+            KeyCode::Left => self.move_cursor_left(),
+            KeyCode::Right => self.move_cursor_right(),
+            KeyCode::Home => self.move_cursor_to_start(),
+            KeyCode::End => self.move_cursor_to_end(),
+            _ => {}
+        }
+    }
+
+    // the search box edits `search_input` with the same cursor, refiltering as it goes
+    pub fn handle_search_key(&mut self, code: KeyCode) {
+        let length = self.search_input.chars().count();
+        match code {
+            KeyCode::Char(c) => {
+                let index = byte_index(&self.search_input, self.cursor_pos);
+                self.search_input.insert(index, c);
+                self.cursor_pos += 1;
+            }
+            KeyCode::Backspace if self.cursor_pos > 0 => {
+                self.cursor_pos -= 1;
+                let index = byte_index(&self.search_input, self.cursor_pos);
+                self.search_input.remove(index);
+            }
+            KeyCode::Delete if self.cursor_pos < length => {
+                let index = byte_index(&self.search_input, self.cursor_pos);
+                self.search_input.remove(index);
+            }
+            KeyCode::Left => self.cursor_pos = self.cursor_pos.saturating_sub(1),
+            KeyCode::Right => self.cursor_pos = (self.cursor_pos + 1).min(length),
+            KeyCode::Home => self.cursor_pos = 0,
+            KeyCode::End => self.cursor_pos = length,
+            _ => return,
+        }
+        self.filter_todos();
+    }
+
+    pub fn show_details(&mut self) {
+        if self.selected_index.is_some() {
+            self.input_mode = InputMode::Details;
+        }
+    }
+
+    pub fn start_editing(&mut self) {
+        if let Some(todo) = self.selected_todo() {
+            self.input_buffer = todo.input_text();
+            self.original_input = self.input_buffer.clone();
+            self.move_cursor_to_end();
+            self.input_mode = InputMode::Editing;
+        }
+    }
+
+    // clearing the text deletes the todo, like the delete key it goes to the trash and
+    // can be undone
+    pub fn save_edit(&mut self) {
+        let (text, tags) = parse_tags(&self.input_buffer);
+        if text.is_empty() {
+            if tags.is_empty() {
+                self.delete_selected_todo();
+                self.cancel_edit();
+            } else {
+                self.set_error("A todo needs some text besides its tags");
+            }
+            return;
+        }
+        let before = self.current_lists();
+        if let Some(todo) = self.selected_todo_mut() {
+            todo.text = text;
+            todo.tags = tags;
+            todo.touch();
+            self.original_input = self.input_buffer.clone();
+            self.push_undo(before);
+            self.filter_todos();
+            self.persist();
+        }
+    }
+
+    pub fn toggle_preview(&mut self) {
+        self.show_preview = !self.show_preview;
+    }
+
+    pub fn start_editing_notes(&mut self) {
+        if let Some(notes) = self.selected_todo().map(|todo| todo.notes.clone()) {
+            self.input_buffer = notes.clone();
+            self.original_input = notes;
+            self.move_cursor_to_end();
+            self.input_mode = InputMode::EditingNotes;
+        }
+    }
+
+    pub fn save_notes(&mut self) {
+        let notes = mem::take(&mut self.input_buffer);
+        self.cursor_pos = 0;
+        self.input_mode = InputMode::Normal;
+        let before = self.current_lists();
+        if let Some(todo) = self.selected_todo_mut() {
+            if todo.notes == notes {
+                return;
+            }
+            todo.notes = notes;
+            todo.touch();
+            self.push_undo(before);
+            self.filter_todos();
+            self.persist();
+        }
+    }
+
+    // Esc while adding or editing, only asks when there's something to lose
+    pub fn cancel_input(&mut self) {
+        if self.input_buffer == self.original_input {
+            self.cancel_edit();
+        } else {
+            self.discard_from = mem::replace(&mut self.input_mode, InputMode::DiscardConfirm);
+        }
+    }
+
+    // "no" in the discard prompt
+    pub fn keep_editing(&mut self) {
+        self.input_mode = mem::take(&mut self.discard_from);
+    }
+
+    pub fn cancel_edit(&mut self) {
+        self.input_buffer.clear();
+        self.cursor_pos = 0;
+        self.adding_parent = None;
+        self.input_mode = InputMode::Normal;
+    }
+}
+
+// byte offset of the char index `cursor` in `text`, or its end
+fn byte_index(text: &str, cursor: usize) -> usize {
+    text.char_indices()
+        .nth(cursor)
+        .map(|(i, _)| i)
+        .unwrap_or(text.len())
+}
+
+// `buffer` without the word before the char index `cursor` (and the whitespace between
+// them), like ctrl+w in a shell. returns the new buffer and cursor
+fn delete_word_before(buffer: &str, cursor: usize) -> (String, usize) {
+    let chars: Vec<char> = buffer.chars().collect();
+    let cursor = cursor.min(chars.len());
+    let mut start = cursor;
+    while start > 0 && chars[start - 1].is_whitespace() {
+        start -= 1;
+    }
+    while start > 0 && !chars[start - 1].is_whitespace() {
+        start -= 1;
+    }
+    let remaining = chars[..start].iter().chain(&chars[cursor..]).collect();
+    (remaining, start)
+}
+
+// every non-overlapping occurrence of `query` in `text`, as char ranges. with
+// `whole_word` only the ones that aren't part of a longer word
+pub fn match_ranges(
+    text: &str,
+    query: &str,
+    case_sensitive: bool,
+    whole_word: bool,
+) -> Vec<Range<usize>> {
+    let normalize = |c: char| {
+        if case_sensitive {
+            c
+        } else {
+            c.to_lowercase().next().unwrap_or(c)
+        }
+    };
+    let text: Vec<char> = text.chars().map(normalize).collect();
+    let query: Vec<char> = query.chars().map(normalize).collect();
+    let is_word_char = |index: usize| text.get(index).is_some_and(|c| c.is_alphanumeric());
+
+    let mut ranges = Vec::new();
+    if query.is_empty() {
+        return ranges;
+    }
+    let mut start = 0;
+    while start + query.len() <= text.len() {
+        let end = start + query.len();
+        let at_word_bounds = (start == 0 || !is_word_char(start - 1)) && !is_word_char(end);
+        if text[start..end] == query[..] && (!whole_word || at_word_bounds) {
+            ranges.push(start..end);
+            start = end;
+        } else {
+            start += 1;
+        }
+    }
+    ranges
+}
+
+// scores `query` as a subsequence of `text`, with the (char) indices of the matched
+// chars, `None` when it doesn't match at all. consecutive matches and matches at the
+// start of a word score higher, so "bta" ranks "Build a TUI app" above "Bring the laptop"
+pub fn fuzzy_match(text: &str, query: &str, case_sensitive: bool) -> Option<(i64, Vec<usize>)> {
+    let matcher = if case_sensitive {
+        SkimMatcherV2::default().respect_case()
+    } else {
+        SkimMatcherV2::default().ignore_case()
+    };
+    matcher.fuzzy_indices(text, query)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn app_with(texts: &[&str]) -> App {
+        App::from_lines(texts.iter().map(|text| text.to_string()))
+    }
+
+    fn texts<'a>(todos: impl IntoIterator<Item = &'a Todo>) -> Vec<&'a str> {
+        todos.into_iter().map(|todo| todo.text.as_str()).collect()
+    }
+
+    // an empty directory of its own, so tests running at the same time don't
+    // share data or archive files
+    fn test_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("ratatui-todo-{}-test", name));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    #[test]
+    fn selection_wraps_around() {
+        let mut app = app_with(&["one", "two", "three"]);
+        assert_eq!(app.selected_index, Some(0));
+
+        app.move_selection_up();
+        assert_eq!(app.selected_index, Some(2));
+
+        app.move_selection_down();
+        assert_eq!(app.selected_index, Some(0));
+    }
+
+    #[test]
+    fn moving_selection_in_an_empty_list_does_not_panic() {
+        let mut app = app_with(&[]);
+        app.move_selection_down();
+        assert_eq!(app.selected_index, None);
+        app.move_selection_up();
+        assert_eq!(app.selected_index, None);
+
+        // a stale selection left over from a non-empty list
+        let moves: [fn(&mut App); 5] = [
+            App::move_selection_down,
+            App::move_selection_up,
+            App::page_down,
+            App::page_up,
+            |app| app.jump_to(3),
+        ];
+        for move_selection in moves {
+            app.selected_index = Some(0);
+            move_selection(&mut app);
+            assert_eq!(app.selected_index, None);
+        }
+    }
+
+    #[test]
+    fn filter_is_case_insensitive_substring_by_default() {
+        let mut app = app_with(&["Learn Rust", "Build a TUI app", "rusty nails"]);
+        app.search_input = "rust".to_string();
+        app.filter_todos();
+        assert_eq!(texts(app.shown_todos()), ["Learn Rust", "rusty nails"]);
+
+        app.search_input.clear();
+        app.filter_todos();
+        assert_eq!(app.filtered_todos.len(), 3);
+    }
+
+    #[test]
+    fn filtering_everything_out_clears_the_selection() {
+        let mut app = app_with(&["one", "two"]);
+        app.search_input = "zzz".to_string();
+        app.filter_todos();
+        assert_eq!(app.selected_index, None);
+    }
+
+    #[test]
+    fn tag_search_matches_either_tag() {
+        let mut app = app_with(&["buy milk #shopping #urgent", "call mom"]);
+        assert_eq!(app.todos[0].text, "buy milk");
+        assert_eq!(app.todos[0].tags, ["shopping", "urgent"]);
+
+        for query in ["#shopping", "#urgent"] {
+            app.search_input = query.to_string();
+            app.filter_todos();
+            assert_eq!(texts(app.shown_todos()), ["buy milk"]);
+        }
+    }
+
+    #[test]
+    fn add_todo_appends_and_ignores_empty_input() {
+        let mut app = app_with(&["one"]);
+        app.input_buffer = "two".to_string();
+        app.add_todo();
+        assert_eq!(texts(&app.todos), ["one", "two"]);
+        assert!(app.input_buffer.is_empty());
+
+        app.add_todo();
+        assert_eq!(app.todos.len(), 2);
+    }
+
+    #[test]
+    fn delete_removes_the_selected_todo() {
+        let mut app = app_with(&["one", "two", "three"]);
+        app.selected_index = Some(2);
+        app.delete_selected_todo();
+        assert_eq!(texts(&app.todos), ["one", "two"]);
+        // selection moves to the new last item
+        assert_eq!(app.selected_index, Some(1));
+        // and the status bar says what happened
+        let message = app.message.as_ref().unwrap();
+        assert_eq!(message.text, "Deleted 'three' (u to undo)");
+        assert_eq!(message.severity, Severity::Info);
+    }
+
+    #[test]
+    fn deleting_a_duplicate_removes_the_selected_copy() {
+        let mut app = app_with(&["Call mom", "Call mom"]);
+        let first_id = app.todos[0].id;
+        app.selected_index = Some(1);
+        app.delete_selected_todo();
+        assert_eq!(app.todos.len(), 1);
+        assert_eq!(app.todos[0].id, first_id);
+    }
+
+    #[test]
+    fn deleting_a_duplicate_in_the_search_results_removes_that_copy() {
+        let mut app = app_with(&["Call mom", "buy milk", "Call mom"]);
+        let first_id = app.todos[0].id;
+        app.search_input = "call".to_string();
+        app.filter_todos();
+        app.selected_index = Some(1);
+        app.delete_selected_todo();
+        assert_eq!(texts(&app.todos), ["Call mom", "buy milk"]);
+        assert_eq!(app.todos[0].id, first_id);
+    }
+
+    #[test]
+    fn add_edit_and_delete_go_by_id() {
+        let mut app = app_with(&["same", "same"]);
+        let ids: Vec<u64> = app.todos.iter().map(|todo| todo.id).collect();
+        assert_eq!(ids, [1, 2]);
+
+        // editing the second copy leaves the first alone
+        app.selected_index = Some(1);
+        app.start_editing();
+        app.input_buffer = "changed".to_string();
+        app.save_edit();
+        assert_eq!(texts(&app.todos), ["same", "changed"]);
+        assert_eq!(app.todos[1].id, 2);
+
+        app.delete_selected_todo();
+        app.input_buffer = "new".to_string();
+        app.add_todo();
+        // ids keep counting up, a deleted todo's id isn't handed out again
+        let ids: Vec<u64> = app.todos.iter().map(|todo| todo.id).collect();
+        assert_eq!(ids, [1, 3]);
+        assert_eq!(texts(&app.todos), ["same", "new"]);
+    }
+
+    #[test]
+    fn the_shown_todos_are_the_stored_ones_not_copies() {
+        let mut app = app_with(&["one", "two"]);
+        assert_eq!(app.filtered_todos, [1, 2]);
+
+        app.todos[1].text = "changed".to_string();
+        assert_eq!(texts(app.shown_todos()), ["one", "changed"]);
+        assert_eq!(app.selected_todo().map(|todo| todo.id), Some(1));
+    }
+
+    #[test]
+    fn reordering_swaps_with_the_neighbour() {
+        let mut app = app_with(&["one", "two", "three"]);
+        app.move_todo_down();
+        assert_eq!(texts(&app.todos), ["two", "one", "three"]);
+        assert_eq!(app.selected_index, Some(1));
+
+        app.move_todo_up();
+        app.move_todo_up(); // already at the top
+        assert_eq!(texts(&app.todos), ["one", "two", "three"]);
+        assert_eq!(app.selected_index, Some(0));
+
+        app.select_last();
+        app.move_todo_down(); // already at the bottom
+        assert_eq!(texts(&app.todos), ["one", "two", "three"]);
+        assert_eq!(app.selected_index, Some(2));
+    }
+
+    #[test]
+    fn reordering_switches_to_manual_order_and_keeps_it() {
+        let mut app = app_with(&["b", "a", "c"]);
+        app.sort_mode = SortMode::Alphabetical;
+        app.sort_todos();
+        app.move_todo_down();
+        assert!(app.sort_mode == SortMode::Manual);
+        assert_eq!(texts(&app.todos), ["a", "c", "b"]);
+
+        // sorting by "manual" leaves the order alone
+        app.run_command("sort manual");
+        assert_eq!(texts(&app.todos), ["a", "c", "b"]);
+    }
+
+    #[test]
+    fn reordering_a_sorted_list_can_be_undone_back_to_the_manual_order() {
+        let mut app = app_with(&["b", "c", "a"]);
+        app.run_command("sort alphabetical");
+        // "a" is shown first, with nothing above it to swap with
+        app.select_first();
+        app.move_todo_up();
+        assert!(app.sort_mode == SortMode::Alphabetical);
+        assert_eq!(texts(&app.todos), ["b", "c", "a"]);
+
+        app.move_todo_down();
+        assert_eq!(texts(&app.todos), ["b", "a", "c"]);
+        app.undo();
+        assert_eq!(texts(&app.todos), ["b", "c", "a"]);
+        assert_eq!(texts(app.shown_todos()), ["b", "c", "a"]);
+    }
+
+    #[test]
+    fn sorting_only_orders_the_view_and_manual_brings_the_order_back() {
+        let mut app = app_with(&["b", "c", "a"]);
+        app.todos[0].children.push(Todo::new(10, "z".to_string()));
+        app.todos[0].children.push(Todo::new(11, "y".to_string()));
+        app.sort_mode = SortMode::Alphabetical;
+        app.sort_todos();
+        assert_eq!(texts(app.shown_todos()), ["a", "b", "y", "z", "c"]);
+        assert_eq!(texts(&app.todos), ["b", "c", "a"]);
+        // still on "b"
+        assert_eq!(app.selected_index, Some(1));
+
+        app.run_command("sort manual");
+        assert_eq!(texts(app.shown_todos()), ["b", "z", "y", "c", "a"]);
+    }
+
+    #[test]
+    fn reordering_is_disabled_while_filtered() {
+        let mut app = app_with(&["one", "two", "three"]);
+        app.search_input = "t".to_string();
+        app.filter_todos();
+        app.move_todo_down();
+        assert_eq!(texts(&app.todos), ["one", "two", "three"]);
+
+        // "two" would swap with the hidden done "three"
+        app.clear_search();
+        app.select_last();
+        app.toggle_selected();
+        app.cycle_status_filter();
+        app.select_last();
+        app.move_todo_down();
+        assert_eq!(texts(&app.todos), ["one", "two", "three"]);
+    }
+
+    #[test]
+    fn undo_puts_a_deleted_todo_back_where_it_was() {
+        let mut app = app_with(&["one", "two", "three"]);
+        app.selected_index = Some(1);
+        let id = app.todos[1].id;
+        app.delete_selected_todo();
+        app.undo();
+        assert_eq!(texts(&app.todos), ["one", "two", "three"]);
+        assert_eq!(app.todos[1].id, id);
+    }
+
+    #[test]
+    fn toggling_and_reordering_can_be_undone() {
+        let mut app = app_with(&["one", "two"]);
+        app.toggle_selected();
+        app.move_todo_down();
+        assert_eq!(texts(&app.todos), ["two", "one"]);
+
+        app.undo();
+        assert_eq!(texts(&app.todos), ["one", "two"]);
+        assert!(app.todos[0].done);
+        app.undo();
+        assert!(!app.todos[0].done);
+
+        // a new change drops what could have been redone
+        app.raise_selected_priority();
+        app.redo();
+        assert!(!app.todos[0].done);
+        assert!(app.todos[0].priority == Priority::High);
+    }
+
+    #[test]
+    fn whole_word_search_skips_matches_inside_words() {
+        let mut app = app_with(&["feed the cat", "concatenate files", "Cat toys"]);
+        app.search_input = "cat".to_string();
+        app.toggle_whole_word();
+        assert_eq!(texts(app.shown_todos()), ["feed the cat", "Cat toys"]);
+        app.toggle_case_sensitive();
+        assert_eq!(texts(app.shown_todos()), ["feed the cat"]);
+
+        // clearing the search resets both
+        app.clear_search();
+        assert!(!app.whole_word && !app.case_sensitive);
+    }
+
+    #[test]
+    fn fuzzy_search_finds_scattered_letters_best_match_first() {
+        let mut app = app_with(&["Bring the laptop", "Build a TUI app", "call mom"]);
+        app.toggle_search_mode();
+        app.search_input = "bta".to_string();
+        app.filter_todos();
+        assert_eq!(
+            texts(app.shown_todos()),
+            ["Build a TUI app", "Bring the laptop"]
+        );
+
+        // an empty search is the whole list in its usual order
+        app.clear_search();
+        assert_eq!(
+            texts(app.shown_todos()),
+            ["Bring the laptop", "Build a TUI app", "call mom"]
+        );
+    }
+
+    #[test]
+    fn undo_restores_a_deleted_todo() {
+        let mut app = app_with(&["one", "two"]);
+        app.delete_selected_todo();
+        assert_eq!(texts(&app.todos), ["two"]);
+
+        app.undo();
+        assert_eq!(texts(&app.todos), ["one", "two"]);
+
+        app.redo();
+        assert_eq!(texts(&app.todos), ["two"]);
+    }
+
+    #[test]
+    fn clicking_a_row_selects_it_and_double_clicking_edits_it() {
+        let mut app = app_with(&["one", "two", "three"]);
+        app.list_area = Rect::new(0, 3, 20, 10);
+        let now = Instant::now();
+
+        // first row of todos is just below the top border
+        app.click(5, 5, now);
+        assert_eq!(app.selected_index, Some(1));
+        assert!(matches!(app.input_mode, InputMode::Normal));
+
+        app.click(5, 5, now + Duration::from_millis(100));
+        assert!(matches!(app.input_mode, InputMode::Editing));
+        assert_eq!(app.input_buffer, "two");
+    }
+
+    #[test]
+    fn clicks_on_the_border_or_below_the_last_todo_are_ignored() {
+        let mut app = app_with(&["one", "two"]);
+        app.list_area = Rect::new(0, 3, 20, 10);
+        let now = Instant::now();
+
+        app.click(5, 3, now);
+        app.click(5, 8, now);
+        assert_eq!(app.selected_index, Some(0));
+    }
+
+    #[test]
+    fn summary_counts_all_todos_not_just_the_shown_ones() {
+        let mut app = app_with(&["one", "two", "three"]);
+        app.toggle_selected();
+        app.search_input = "two".to_string();
+        app.filter_todos();
+        assert_eq!(app.summary(), "3 todos, 1 done");
+    }
+
+    #[test]
+    fn deleting_marked_todos_removes_all_of_them() {
+        let mut app = app_with(&["one", "two", "three", "four", "five"]);
+        for index in [0, 2, 3] {
+            app.selected_index = Some(index);
+            app.toggle_mark();
+        }
+
+        app.delete_marked_todos();
+        assert_eq!(texts(&app.todos), ["two", "five"]);
+        assert!(app.marked.is_empty());
+    }
+
+    #[test]
+    fn toggling_flips_done_and_survives_filtering() {
+        let mut app = app_with(&["one", "two"]);
+        app.toggle_selected();
+        assert!(app.todos[0].done);
+        app.search_input = "o".to_string();
+        app.filter_todos();
+        assert!(app.shown_todos()[0].done);
+        assert!(app.todos[0].done);
+
+        app.toggle_selected();
+        assert!(!app.todos[0].done);
+    }
+
+    #[test]
+    fn marks_are_dropped_when_filtered_out() {
+        let mut app = app_with(&["one", "two"]);
+        app.toggle_mark();
+        app.search_input = "two".to_string();
+        app.filter_todos();
+        assert!(app.marked.is_empty());
+    }
+
+    #[test]
+    fn archiving_moves_done_todos_and_restoring_brings_them_back() {
+        let mut app = app_with(&["one", "two", "three"]);
+        app.toggle_selected();
+        app.selected_index = Some(2);
+        app.toggle_selected();
+
+        app.archive_completed();
+        assert_eq!(texts(&app.todos), ["two"]);
+        assert_eq!(texts(&app.archived), ["one", "three"]);
+
+        app.toggle_view();
+        assert_eq!(texts(app.shown_todos()), ["one", "three"]);
+        app.restore_selected();
+        assert_eq!(texts(&app.todos), ["two", "one"]);
+        assert_eq!(texts(&app.archived), ["three"]);
+
+        // undo puts both lists back
+        app.undo();
+        app.undo();
+        assert_eq!(texts(&app.todos), ["one", "two", "three"]);
+        assert!(app.archived.is_empty());
+    }
+
+    #[test]
+    fn deleted_todos_can_be_restored_from_the_trash() {
+        let mut app = app_with(&["one", "two", "three"]);
+        app.selected_index = Some(1);
+        app.delete_selected_todo();
+        assert_eq!(texts(&app.todos), ["one", "three"]);
+        assert_eq!(texts(&app.trash), ["two"]);
+
+        app.view = View::Trash;
+        app.filter_todos();
+        app.restore_selected();
+        // back where it was
+        assert_eq!(texts(&app.todos), ["one", "two", "three"]);
+        assert!(app.trash.is_empty());
+    }
+
+    #[test]
+    fn clicks_on_a_wrapped_todo_select_it() {
+        let mut app = app_with(&["one", "two", "three"]);
+        app.list_area = Rect::new(0, 0, 20, 10);
+        app.item_heights = vec![1, 3, 1];
+
+        app.click(5, 4, Instant::now());
+        assert_eq!(app.selected_index, Some(1));
+        app.click(5, 5, Instant::now());
+        assert_eq!(app.selected_index, Some(2));
+    }
+
+    #[test]
+    fn the_wheel_scrolls_the_view_and_only_drags_the_selection_at_the_edge() {
+        let texts: Vec<String> = (1..=10).map(|i| i.to_string()).collect();
+        let mut app = App::from_lines(texts.into_iter());
+        // 4 rows inside the border
+        app.list_area = Rect::new(0, 0, 20, 6);
+        app.item_heights = vec![1; 10];
+        app.selected_index = Some(2);
+
+        app.scroll_down();
+        app.scroll_down();
+        assert_eq!(app.list_state.offset(), 2);
+        assert_eq!(app.selected_index, Some(2));
+        app.scroll_down();
+        assert_eq!(app.selected_index, Some(3));
+
+        // it stops once the last todo is at the bottom
+        for _ in 0..10 {
+            app.scroll_down();
+        }
+        assert_eq!(app.list_state.offset(), 6);
+        app.scroll_up();
+        assert_eq!(app.list_state.offset(), 5);
+        assert_eq!(app.selected_index, Some(6));
+        app.selected_index = Some(5);
+        app.scroll_up();
+        assert_eq!(app.selected_index, Some(5));
+    }
+
+    #[test]
+    fn input_whitespace_is_trimmed_and_collapsed() {
+        let mut app = app_with(&[]);
+        app.input_buffer = "  hello  ".to_string();
+        app.add_todo();
+        app.input_buffer = "buy \t  milk".to_string();
+        app.add_todo();
+        assert_eq!(texts(&app.todos), ["hello", "buy milk"]);
+    }
+
+    #[test]
+    fn whitespace_only_input_is_rejected() {
+        let mut app = app_with(&["one"]);
+        app.input_buffer = "   ".to_string();
+        app.add_todo();
+        assert_eq!(texts(&app.todos), ["one"]);
+    }
+
+    #[test]
+    fn editing_a_todo_to_nothing_deletes_it() {
+        let mut app = app_with(&["one", "two"]);
+        app.start_editing();
+        app.input_buffer = "#work".to_string();
+        app.save_edit();
+        assert_eq!(texts(&app.todos), ["one", "two"]);
+        assert_eq!(app.message.as_ref().unwrap().severity, Severity::Error);
+
+        app.input_buffer = " \t ".to_string();
+        app.save_edit();
+        assert_eq!(texts(&app.todos), ["two"]);
+        assert_eq!(texts(&app.trash), ["one"]);
+        assert!(matches!(app.input_mode, InputMode::Normal));
+        app.undo();
+        assert_eq!(texts(&app.todos), ["one", "two"]);
+    }
+
+    #[test]
+    fn clearing_all_todos_can_be_undone() {
+        let mut app = app_with(&["one", "two", "three"]);
+        app.clear_all();
+        assert!(app.todos.is_empty());
+        assert!(app.filtered_todos.is_empty());
+        assert_eq!(app.selected_index, None);
+
+        app.undo();
+        assert_eq!(texts(&app.todos), ["one", "two", "three"]);
+    }
+
+    #[test]
+    fn toggle_all_flips_every_todo_both_ways() {
+        let mut app = app_with(&["one", "two", "three"]);
+        app.toggle_selected();
+
+        app.toggle_all();
+        assert!(app.todos.iter().all(|todo| todo.done));
+
+        app.toggle_all();
+        assert!(app.todos.iter().all(|todo| !todo.done));
+    }
+
+    #[test]
+    fn toggle_all_only_touches_the_search_matches() {
+        let mut app = app_with(&["one #work", "two", "three #work"]);
+        app.search_input = "#work".to_string();
+        app.filter_todos();
+
+        app.toggle_all();
+        let done: Vec<bool> = app.todos.iter().map(|todo| todo.done).collect();
+        assert_eq!(done, [true, false, true]);
+    }
+
+    #[test]
+    fn commands_add_sort_and_delete() {
+        let mut app = app_with(&["b"]);
+        app.run_command("add a #new");
+        assert_eq!(texts(&app.todos), ["b", "a"]);
+        assert_eq!(app.todos[1].tags, ["new"]);
+
+        app.run_command("sort alphabetical");
+        assert_eq!(texts(app.shown_todos()), ["a", "b"]);
+
+        app.select_first();
+        app.run_command("delete");
+        assert_eq!(texts(&app.todos), ["b"]);
+    }
+
+    #[test]
+    fn unknown_commands_show_an_error() {
+        let mut app = app_with(&["one"]);
+        app.run_command("frobnicate now");
+        let message = app.message.as_ref().unwrap();
+        assert_eq!(message.text, "Unknown command: frobnicate now");
+        assert_eq!(message.severity, Severity::Error);
+        app.run_command("sort sideways");
+        assert!(app.message.is_some());
+        assert!(!app.should_quit);
+    }
+
+    #[test]
+    fn jumping_to_a_line_number_clamps_to_the_last_todo() {
+        let mut app = app_with(&["one", "two", "three"]);
+        app.jump_to(2);
+        assert_eq!(app.selected_index, Some(1));
+        app.jump_to(40);
+        assert_eq!(app.selected_index, Some(2));
+    }
+
+    #[test]
+    fn priority_cycles_up_and_down_and_wraps_at_the_ends() {
+        let mut app = app_with(&["one"]);
+        app.raise_selected_priority();
+        assert!(app.todos[0].priority == Priority::High);
+        app.raise_selected_priority();
+        assert!(app.todos[0].priority == Priority::Low);
+
+        app.lower_selected_priority();
+        assert!(app.todos[0].priority == Priority::High);
+        app.lower_selected_priority();
+        app.lower_selected_priority();
+        assert!(app.todos[0].priority == Priority::Low);
+    }
+
+    #[test]
+    fn priority_is_saved_and_defaults_to_medium() {
+        let mut todo = Todo::new(1, "one".to_string());
+        todo.priority = Priority::High;
+        let json = serde_json::to_string(&todo).unwrap();
+        let loaded: Todo = serde_json::from_str(&json).unwrap();
+        assert!(loaded.priority == Priority::High);
+
+        let old: Todo = serde_json::from_str(r#"{"text": "two"}"#).unwrap();
+        assert!(old.priority == Priority::Medium);
+    }
+
+    #[test]
+    fn the_selection_survives_a_save_and_load() {
+        let dir = test_dir("selection");
+        let path = dir.join("todos.json");
+        let mut app = app_with(&["one", "two", "three"]);
+        app.selected_index = Some(2);
+        app.save(&path).unwrap();
+
+        let loaded = App::load(&path).unwrap();
+        fs::remove_dir_all(&dir).unwrap();
+        assert_eq!(loaded.selected_id(), app.selected_id());
+    }
+
+    #[test]
+    fn plain_todo_arrays_still_load() {
+        let dir = test_dir("old-format");
+        let path = dir.join("todos.json");
+        fs::write(&path, r#"[{"text": "one"}, {"text": "two"}]"#).unwrap();
+        let loaded = App::load(&path).unwrap();
+        fs::remove_dir_all(&dir).unwrap();
+        assert_eq!(texts(&loaded.todos), ["one", "two"]);
+        assert_eq!(loaded.selected_index, Some(0));
+    }
+
+    #[test]
+    fn a_missing_file_starts_empty_and_a_broken_one_is_an_error() {
+        let dir = test_dir("missing");
+        let path = dir.join("todos.json");
+        let loaded = App::load(&path).unwrap();
+        assert!(loaded.todos.is_empty());
+        assert_eq!(loaded.selected_index, None);
+
+        fs::write(&path, "[{\"text\": ").unwrap();
+        let result = App::load(&path);
+        fs::remove_dir_all(&dir).unwrap();
+        assert!(matches!(result, Err(AppError::Parse(_))));
+    }
+
+    #[test]
+    fn todos_without_timestamps_date_from_the_file() {
+        let dir = test_dir("timestamps");
+        let path = dir.join("todos.json");
+        fs::write(
+            &path,
+            r#"[{"text": "old"}, {"text": "new", "created_at": "2024-01-02T03:04:05+00:00"}]"#,
+        )
+        .unwrap();
+        let modified = DateTime::<Local>::from(fs::metadata(&path).unwrap().modified().unwrap());
+        let loaded = App::load(&path).unwrap();
+        fs::remove_dir_all(&dir).unwrap();
+        assert_eq!(loaded.todos[0].created_at, modified);
+        assert_eq!(loaded.todos[0].updated_at, modified);
+        let saved_at = DateTime::parse_from_rfc3339("2024-01-02T03:04:05+00:00").unwrap();
+        assert_eq!(loaded.todos[1].created_at, saved_at);
+        // and they're written back as RFC 3339
+        let saved = serde_json::to_value(&loaded.todos[1]).unwrap();
+        let created_at = saved["created_at"].as_str().unwrap();
+        assert_eq!(DateTime::parse_from_rfc3339(created_at).unwrap(), saved_at);
+    }
+
+    #[test]
+    fn autosave_waits_for_the_changes_to_settle() {
+        let dir = test_dir("autosave");
+        let path = dir.join("todos.json");
+        let mut app = app_with(&["one"]);
+        app.data_path = Some(path.clone());
+        app.input_buffer = "two".to_string();
+        app.add_todo();
+        let changed_at = app.last_change.unwrap();
+
+        app.maybe_autosave(changed_at + Duration::from_millis(200));
+        assert!(app.dirty);
+        assert!(!path.exists());
+
+        app.maybe_autosave(changed_at + Duration::from_secs(2));
+        assert!(!app.dirty);
+        let saved = App::load(&path).unwrap();
+        fs::remove_dir_all(&dir).unwrap();
+        assert_eq!(texts(&saved.todos), ["one", "two"]);
+    }
+
+    // "parent" with the subtasks "a" and "b"
+    fn app_with_subtasks() -> App {
+        let mut app = app_with(&["parent", "other"]);
+        for text in ["a", "b"] {
+            app.select_first();
+            app.start_adding_subtask();
+            app.input_buffer = text.to_string();
+            app.add_todo();
+        }
+        app
+    }
+
+    #[test]
+    fn subtasks_are_shown_under_their_parent() {
+        let app = app_with_subtasks();
+        assert_eq!(texts(&app.todos), ["parent", "other"]);
+        assert_eq!(texts(&app.todos[0].children), ["a", "b"]);
+        assert_eq!(texts(app.shown_todos()), ["parent", "a", "b", "other"]);
+        assert_eq!(app.row_depths, [0, 1, 1, 0]);
+    }
+
+    #[test]
+    fn collapsing_hides_the_subtasks_from_navigation() {
+        let mut app = app_with_subtasks();
+        app.select_first();
+        app.set_selected_collapsed(true);
+        assert_eq!(texts(app.shown_todos()), ["parent", "other"]);
+
+        app.move_selection_down();
+        assert_eq!(app.selected_id(), Some(app.todos[1].id));
+    }
+
+    #[test]
+    fn completing_a_parent_completes_its_subtasks() {
+        let mut app = app_with_subtasks();
+        app.select_first();
+        app.toggle_selected();
+        assert!(app.todos[0].children.iter().all(|todo| todo.done));
+    }
+
+    #[test]
+    fn archiving_takes_the_done_subtasks_of_active_todos() {
+        let mut app = app_with_subtasks();
+        app.move_selection_down();
+        app.toggle_selected();
+
+        app.archive_completed();
+        assert_eq!(texts(&app.todos[0].children), ["b"]);
+        assert_eq!(texts(&app.archived), ["a"]);
+    }
+
+    #[test]
+    fn toggle_all_completes_the_hidden_subtasks_too() {
+        let mut app = app_with_subtasks();
+        app.select_first();
+        app.set_selected_collapsed(true);
+
+        app.toggle_all();
+        assert!(app.todos[0].children.iter().all(|todo| todo.done));
+    }
+
+    #[test]
+    fn subtasks_can_be_edited_and_deleted() {
+        let mut app = app_with_subtasks();
+        app.selected_index = Some(2);
+        app.start_editing();
+        app.input_buffer = "b2".to_string();
+        app.save_edit();
+        assert_eq!(texts(&app.todos[0].children), ["a", "b2"]);
+
+        app.selected_index = Some(1);
+        app.delete_selected_todo();
+        assert_eq!(texts(&app.todos[0].children), ["b2"]);
+        assert_eq!(texts(&app.trash), ["a"]);
+    }
+
+    #[test]
+    fn progress_counts_the_done_subtasks() {
+        let mut app = app_with_subtasks();
+        assert_eq!(app.todos[0].progress(), Some((0, 2)));
+        assert_eq!(app.todos[1].progress(), None);
+
+        app.selected_index = Some(1);
+        app.toggle_selected();
+        assert_eq!(app.todos[0].progress(), Some((1, 2)));
+    }
+
+    #[test]
+    fn status_filter_combines_with_the_search() {
+        let mut app = app_with(&["buy milk", "buy eggs", "walk"]);
+        app.toggle_selected();
+        app.search_input = "buy".to_string();
+
+        app.cycle_status_filter();
+        assert!(app.status_filter == StatusFilter::Active);
+        assert_eq!(texts(app.shown_todos()), ["buy eggs"]);
+
+        app.cycle_status_filter();
+        assert_eq!(texts(app.shown_todos()), ["buy milk"]);
+
+        app.cycle_status_filter();
+        assert_eq!(texts(app.shown_todos()), ["buy milk", "buy eggs"]);
+    }
+
+    #[test]
+    fn status_filter_clamps_the_selection() {
+        let mut app = app_with(&["one", "two", "three"]);
+        app.selected_index = Some(2);
+        app.toggle_selected();
+        // "Active" leaves two todos, the selection moves onto the last of them
+        app.cycle_status_filter();
+        assert_eq!(app.selected_index, Some(1));
+    }
+
+    #[test]
+    fn todos_can_be_filtered_and_grouped_by_creation_date() {
+        let mut app = app_with(&["old", "new", "yesterday"]);
+        app.todos[0].created_at = Local::now() - Duration::from_secs(30 * 24 * 60 * 60);
+        app.todos[2].created_at = Local::now() - Duration::from_secs(24 * 60 * 60);
+        app.cycle_age_filter();
+        assert_eq!(texts(app.shown_todos()), ["new"]);
+
+        app.age_filter = AgeFilter::All;
+        app.toggle_group_by_date();
+        assert_eq!(texts(app.shown_todos()), ["new", "yesterday", "old"]);
+        let headers: Vec<_> = (0..3).map(|i| app.group_header(i)).collect();
+        assert_eq!(headers, [Some("Today"), Some("Yesterday"), Some("Earlier")]);
+        // the headers aren't rows, moving down goes from todo to todo
+        app.select_first();
+        app.move_selection_down();
+        assert_eq!(app.selected_index, Some(1));
+    }
+
+    #[test]
+    fn merging_skips_todos_already_in_the_list() {
+        let mut app = app_with(&["buy milk", "call mom"]);
+        let mut imported = todos_from_lines("call mom\nwrite report\nwrite report\n");
+        // ids from another file would clash with the ones here
+        imported[1].id = 1;
+        assert_eq!(app.merge_todos(imported), (1, 2));
+        assert_eq!(texts(&app.todos), ["buy milk", "call mom", "write report"]);
+        let ids: HashSet<u64> = app.todos.iter().map(|todo| todo.id).collect();
+        assert_eq!(ids.len(), 3);
+        assert_eq!(
+            app.message.unwrap().text,
+            "Imported 1 todos, skipped 2 already in the list"
+        );
+    }
+
+    #[test]
+    fn pasting_goes_in_at_the_cursor() {
+        let mut app = app_with(&["one"]);
+        app.start_editing();
+        app.move_cursor_to_start();
+        app.paste_text("first\nthen ");
+        assert_eq!(app.input_buffer, "first then one");
+        assert_eq!(app.cursor_pos, 11);
+    }
+
+    #[test]
+    fn pasting_several_lines_while_adding_adds_a_todo_per_line() {
+        let mut app = app_with(&["one"]);
+        app.start_adding();
+        app.input_buffer = "buy ".to_string();
+        app.move_cursor_to_end();
+        app.paste_text("milk\n\neggs\nbread\n");
+        assert_eq!(texts(&app.todos), ["one", "buy milk", "eggs", "bread"]);
+        assert!(matches!(app.input_mode, InputMode::Normal));
+    }
+
+    #[test]
+    fn delete_word_before_removes_the_previous_word() {
+        assert_eq!(
+            delete_word_before("buy some milk", 13),
+            ("buy some ".to_string(), 9)
+        );
+        // trailing whitespace goes along with the word
+        assert_eq!(
+            delete_word_before("buy some  ", 10),
+            ("buy ".to_string(), 4)
+        );
+        // only what's before the cursor
+        assert_eq!(
+            delete_word_before("buy some milk", 8),
+            ("buy  milk".to_string(), 4)
+        );
+        assert_eq!(delete_word_before("", 0), (String::new(), 0));
+    }
+
+    #[test]
+    fn delete_word_before_respects_multibyte_chars() {
+        assert_eq!(
+            delete_word_before("café crème", 10),
+            ("café ".to_string(), 5)
+        );
+        assert_eq!(delete_word_before("日本 語", 2), (" 語".to_string(), 0));
+    }
+
+    #[test]
+    fn lists_keep_their_own_todos() {
+        let mut app = app_with(&["one", "two"]);
+        app.run_command("list work");
+        assert_eq!(app.list_names(), ["Todos", "work"]);
+        assert!(app.todos.is_empty());
+        app.run_command("add report");
+
+        app.previous_list();
+        assert_eq!(texts(&app.todos), ["one", "two"]);
+        app.next_list();
+        assert_eq!(texts(&app.todos), ["report"]);
+    }
+
+    #[test]
+    fn lists_survive_a_save_and_load() {
+        let dir = test_dir("lists");
+        let path = dir.join("todos.json");
+        let mut app = app_with(&["one"]);
+        app.open_list("work");
+        app.run_command("add report");
+        app.save(&path).unwrap();
+
+        let mut loaded = App::load(&path).unwrap();
+        fs::remove_dir_all(&dir).unwrap();
+        assert_eq!(loaded.active_list, 1);
+        assert_eq!(texts(&loaded.todos), ["report"]);
+        loaded.switch_list(0);
+        assert_eq!(texts(&loaded.todos), ["one"]);
+    }
+
+    #[test]
+    fn data_files_in_one_directory_keep_their_own_archive() {
+        let dir = test_dir("two-archives");
+        let mut work = app_with(&["report"]);
+        work.toggle_selected();
+        work.archive_completed();
+        work.save(&dir.join("work.json")).unwrap();
+        app_with(&["milk"]).save(&dir.join("home.json")).unwrap();
+        // an archive from before they were named after the data file
+        fs::write(dir.join("archive.json"), r#"[{"text": "old"}]"#).unwrap();
+
+        let work = App::load(&dir.join("work.json")).unwrap();
+        let home = App::load(&dir.join("home.json")).unwrap();
+        let todos = App::load(&dir.join("todos.json")).unwrap();
+        fs::remove_dir_all(&dir).unwrap();
+        assert_eq!(texts(&work.archived), ["report"]);
+        assert!(home.archived.is_empty());
+        assert_eq!(texts(&todos.archived), ["old"]);
+    }
+
+    #[test]
+    fn a_text_file_session_never_writes_a_data_file() {
+        let lines = ["buy milk #shopping", "", "   ", "call mom"];
+        let mut app = App::from_lines(lines.iter().map(|line| line.to_string()));
+        assert_eq!(texts(&app.todos), ["buy milk", "call mom"]);
+        assert_eq!(app.todos[0].tags, ["shopping"]);
+        assert!(app.data_path.is_none());
+
+        // nothing is saved, so quitting after a change asks first
+        app.run_command("add report");
+        assert!(!app.save_on_quit());
+    }
+
+    #[test]
+    fn only_empty_lists_can_be_deleted() {
+        let mut app = app_with(&["one"]);
+        app.open_list("work");
+        app.run_command("add report");
+        app.delete_list();
+        assert_eq!(app.list_names().len(), 2);
+
+        app.clear_all();
+        app.delete_list();
+        assert_eq!(app.list_names(), ["Todos"]);
+        assert_eq!(texts(&app.todos), ["one"]);
+    }
+
+    #[test]
+    fn text_files_import_line_by_line() {
+        let mut app = app_with(&["call mom"]);
+        let path = std::env::temp_dir().join("ratatui-todo-import-lines-test.txt");
+        let long = "x".repeat(10_000);
+        fs::write(
+            &path,
+            format!("  buy milk \r\n\r\ncall mom\r\n{}\r\n", long),
+        )
+        .unwrap();
+        assert_eq!(app.import_lines(&path).unwrap(), (2, 1));
+        assert_eq!(texts(&app.todos), ["call mom", "buy milk", long.as_str()]);
+        assert_eq!(app.filtered_todos.len(), 3);
+
+        fs::write(&path, b"buy \xff milk\n").unwrap();
+        let result = app.import_lines(&path);
+        fs::remove_file(&path).unwrap();
+        assert!(matches!(result, Err(AppError::Io(_))));
+        assert_eq!(app.todos.len(), 3);
+    }
+
+    #[test]
+    fn the_list_exports_to_markdown() {
+        let mut app = app_with(&["buy milk #shopping #urgent", "call mom", "fix bike"]);
+        app.todos[0].priority = Priority::High;
+        app.todos[1].done = true;
+        app.todos[2]
+            .children
+            .push(Todo::new(10, "get a tube".to_string()));
+        assert_eq!(
+            app.markdown(),
+            "# Todos\n\
+             \n\
+             ## Active\n\
+             \n\
+             - [ ] **high** buy milk #shopping #urgent\n\
+             - [ ] **medium** fix bike\n\
+             \x20 - [ ] **medium** get a tube\n\
+             \n\
+             ## Completed\n\
+             \n\
+             - [x] **medium** call mom\n"
+        );
+
+        let path = std::env::temp_dir().join("ratatui-todo-export-test.md");
+        app.export_markdown(&path).unwrap();
+        let written = fs::read_to_string(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+        assert_eq!(written, app.markdown());
+    }
+
+    #[test]
+    fn notes_are_saved_on_the_selected_todo() {
+        let mut app = app_with(&["one", "two"]);
+        app.selected_index = Some(1);
+        app.start_editing_notes();
+        app.insert_str("first line");
+        app.insert_char('\n');
+        app.insert_str("second line");
+        app.save_notes();
+
+        assert!(matches!(app.input_mode, InputMode::Normal));
+        assert_eq!(app.todos[1].notes, "first line\nsecond line");
+        assert!(app.todos[0].notes.is_empty());
+        app.undo();
+        assert!(app.todos[1].notes.is_empty());
+    }
+
+    #[test]
+    fn pasting_into_notes_keeps_the_line_breaks() {
+        let mut app = app_with(&["one"]);
+        app.start_editing_notes();
+        app.paste_text("a\r\n\nb");
+        assert_eq!(app.input_buffer, "a\n\nb");
+    }
+
+    #[test]
+    fn cancelling_an_unchanged_edit_does_not_ask() {
+        let mut app = app_with(&["one"]);
+        app.start_editing();
+        app.cancel_input();
+        assert!(matches!(app.input_mode, InputMode::Normal));
+
+        app.start_adding();
+        app.cancel_input();
+        assert!(matches!(app.input_mode, InputMode::Normal));
+    }
+
+    #[test]
+    fn cancelling_a_changed_edit_asks_first() {
+        let mut app = app_with(&["one"]);
+        app.start_editing();
+        app.insert_char('!');
+        app.cancel_input();
+        assert!(matches!(app.input_mode, InputMode::DiscardConfirm));
+
+        app.keep_editing();
+        assert!(matches!(app.input_mode, InputMode::Editing));
+        assert_eq!(app.input_buffer, "one!");
+
+        app.cancel_input();
+        app.cancel_edit();
+        assert!(matches!(app.input_mode, InputMode::Normal));
+        assert_eq!(texts(&app.todos), ["one"]);
+    }
+
+    #[test]
+    fn typing_stops_at_the_max_length() {
+        let mut app = App {
+            max_len: Some(3),
+            ..App::default()
+        };
+        app.start_adding();
+        for c in "abcd".chars() {
+            app.insert_char(c);
+        }
+        assert_eq!(app.input_buffer, "abc");
+
+        // searches aren't limited
+        app.input_mode = InputMode::Searching;
+        app.insert_char('d');
+        assert_eq!(app.input_buffer, "abcd");
+    }
+
+    #[test]
+    fn marking_all_shown_only_marks_the_matches() {
+        let mut app = app_with(&["buy milk #errand", "write report", "post letter #errand"]);
+        app.search_input = "#errand".to_string();
+        app.filter_todos();
+        app.mark_all_shown();
+        assert_eq!(app.marked.len(), 2);
+
+        app.toggle_marked();
+        app.search_input.clear();
+        app.filter_todos();
+        let done: Vec<bool> = app.todos.iter().map(|todo| todo.done).collect();
+        assert_eq!(done, [true, false, true]);
+    }
+
+    #[test]
+    fn todos_round_trip_through_toml() {
+        let dir = test_dir("toml");
+        let path = dir.join("todos.toml");
+        let mut app = app_with_subtasks();
+        app.todos[0].notes = "some\nnotes".to_string();
+        app.todos[0].tags = vec!["work".to_string()];
+        app.todos[0].priority = Priority::High;
+        app.todos[0].collapsed = true;
+        app.todos[1].done = true;
+        app.archived = vec![Todo::new(99, "old".to_string())];
+        app.filter_todos();
+        app.select_last();
+        app.save(&path).unwrap();
+
+        let loaded = App::load(&path).unwrap();
+        fs::remove_dir_all(&dir).unwrap();
+        assert!(loaded.todos == app.todos);
+        assert!(loaded.archived == app.archived);
+        assert_eq!(loaded.selected_id(), app.selected_id());
+        assert_eq!(archive_path(&path).extension().unwrap(), "toml");
+    }
+
+    #[cfg(feature = "sqlite")]
+    #[test]
+    fn todos_round_trip_through_sqlite() {
+        let dir = test_dir("sqlite");
+        let path = dir.join("todos.db");
+        let mut app = app_with_subtasks();
+        app.open_list("work");
+        app.run_command("add report");
+        app.archived = vec![Todo::new(99, "old".to_string())];
+        app.save(&path).unwrap();
+
+        // only what changed gets written, deletes included
+        app.switch_list(0);
+        app.select_last();
+        app.delete_selected_todo();
+        app.save(&path).unwrap();
+
+        let mut loaded = App::load(&path).unwrap();
+        fs::remove_dir_all(&dir).unwrap();
+        assert!(loaded.todos == app.todos);
+        assert_eq!(texts(&loaded.todos[0].children), ["a", "b"]);
+        assert!(loaded.archived == app.archived);
+        loaded.switch_list(1);
+        assert_eq!(texts(&loaded.todos), ["report"]);
+    }
+
+    #[cfg(feature = "sqlite")]
+    #[test]
+    fn a_new_database_starts_from_the_json_file() {
+        let dir = test_dir("sqlite-migration");
+        let json_path = dir.join("todos.json");
+        app_with(&["one", "two"]).save(&json_path).unwrap();
+
+        let db_path = dir.join("todos.db");
+        App::load(&db_path).unwrap();
+
+        // with the json files gone the todos come from the database
+        fs::remove_file(&json_path).unwrap();
+        fs::remove_file(archive_path(&json_path)).unwrap();
+        let loaded = App::load(&db_path).unwrap();
+        fs::remove_dir_all(&dir).unwrap();
+        assert_eq!(texts(&loaded.todos), ["one", "two"]);
+    }
+
+    #[test]
+    fn messages_expire_after_a_few_seconds() {
+        let mut app = App::default();
+        app.set_message("Saved");
+        let shown_at = app.message.as_ref().unwrap().shown_at;
+        app.expire_message(shown_at + Duration::from_secs(1));
+        assert!(app.message.is_some());
+        app.expire_message(shown_at + MESSAGE_DURATION);
+        assert!(app.message.is_none());
+    }
+
+    #[test]
+    fn filtering_keeps_the_same_todo_selected() {
+        let mut app = app_with(&["apple pie", "banana", "apple juice"]);
+        app.selected_index = Some(2);
+        app.search_input = "apple".to_string();
+        app.filter_todos();
+        assert_eq!(texts(app.shown_todos()), ["apple pie", "apple juice"]);
+        assert_eq!(app.selected_index, Some(1));
+
+        app.search_input = "apple j".to_string();
+        app.filter_todos();
+        assert_eq!(app.selected_index, Some(0));
+        app.clear_search();
+        assert_eq!(app.selected_index, Some(2));
+    }
+
+    #[test]
+    fn the_exit_summary_counts_every_list() {
+        let mut app = app_with(&["one", "two"]);
+        app.toggle_selected();
+        app.open_list("work");
+        app.run_command("add report");
+        assert_eq!(app.exit_summary(), "2 active, 1 completed");
+    }
+
+    #[test]
+    fn n_and_shift_n_skip_done_todos_and_wrap() {
+        let mut app = app_with(&["one", "two", "three", "four"]);
+        for index in [1, 2] {
+            app.selected_index = Some(index);
+            app.toggle_selected();
+        }
+        app.selected_index = Some(0);
+        app.select_next_incomplete();
+        assert_eq!(app.selected_index, Some(3));
+        app.select_next_incomplete();
+        assert_eq!(app.selected_index, Some(0));
+        app.select_prev_incomplete();
+        assert_eq!(app.selected_index, Some(3));
+
+        app.toggle_all();
+        app.select_next_incomplete();
+        assert_eq!(app.selected_index, Some(3));
+    }
+}
@@ -0,0 +1,119 @@
+use std::{ffi::OsString, path::PathBuf};
+
+pub const USAGE: &str = "\
+usage: todo-app-v1 [options] [file]
+
+  file              open the lines of a text file (or piped input), not saved
+  --file <path>     the data file to load and save, todos.json/.toml/.db
+  --search <term>   start with the list filtered
+  --add <text>      add a todo to the saved list and exit
+  --merge <file>    add the todos of a todos.json/.toml or text file
+  --import <file>   add the lines of a text file
+  --help            show this";
+
+// where the todos to start with come from, besides the data file
+#[derive(Debug, PartialEq)]
+pub enum Import {
+    // `todo-app-v1 tasks.txt`, a session of its own that isn't saved
+    Open(PathBuf),
+    // `--merge`, json/toml by extension or text
+    Merge(PathBuf),
+    // `--import`, always text
+    Lines(PathBuf),
+}
+
+#[derive(Debug, Default, PartialEq)]
+pub struct Args {
+    pub file: Option<PathBuf>,
+    pub search: Option<String>,
+    pub add: Option<String>,
+    pub import: Option<Import>,
+    pub help: bool,
+}
+
+// the command line without the program name. errors are meant to be printed
+// followed by the usage
+pub fn parse(args: impl IntoIterator<Item = OsString>) -> Result<Args, String> {
+    let mut parsed = Args::default();
+    let mut args = args.into_iter();
+    while let Some(arg) = args.next() {
+        let flag = arg.to_string_lossy().into_owned();
+        let mut value = || args.next().ok_or(format!("{} needs a value", flag));
+        let import = match flag.as_str() {
+            "--help" | "-h" => {
+                parsed.help = true;
+                None
+            }
+            "--file" => {
+                parsed.file = Some(PathBuf::from(value()?));
+                None
+            }
+            "--search" => {
+                parsed.search = Some(text(value()?, &flag)?);
+                None
+            }
+            "--add" => {
+                parsed.add = Some(text(value()?, &flag)?);
+                None
+            }
+            "--merge" => Some(Import::Merge(PathBuf::from(value()?))),
+            "--import" => Some(Import::Lines(PathBuf::from(value()?))),
+            _ if flag.starts_with('-') && flag != "-" => {
+                return Err(format!("unknown option {}", flag));
+            }
+            _ => Some(Import::Open(PathBuf::from(arg))),
+        };
+        if import.is_some() {
+            if parsed.import.is_some() {
+                return Err("only one file can be imported at a time".to_string());
+            }
+            parsed.import = import;
+        }
+    }
+    if parsed.add.is_some() && parsed.import.is_some() {
+        return Err("--add can't be combined with importing a file".to_string());
+    }
+    Ok(parsed)
+}
+
+fn text(value: OsString, flag: &str) -> Result<String, String> {
+    value
+        .into_string()
+        .map_err(|_| format!("{} needs UTF-8 text", flag))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse_str(args: &[&str]) -> Result<Args, String> {
+        parse(args.iter().map(OsString::from))
+    }
+
+    #[test]
+    fn flags_take_the_next_argument() {
+        let args = parse_str(&["--file", "work.json", "--search", "#urgent"]).unwrap();
+        assert_eq!(args.file, Some(PathBuf::from("work.json")));
+        assert_eq!(args.search.as_deref(), Some("#urgent"));
+        assert_eq!(args.import, None);
+
+        let args = parse_str(&["--add", "buy milk"]).unwrap();
+        assert_eq!(args.add.as_deref(), Some("buy milk"));
+
+        let args = parse_str(&["tasks.txt"]).unwrap();
+        assert_eq!(args.import, Some(Import::Open("tasks.txt".into())));
+        let args = parse_str(&["--import", "todo.txt"]).unwrap();
+        assert_eq!(args.import, Some(Import::Lines("todo.txt".into())));
+    }
+
+    #[test]
+    fn mistakes_are_explained() {
+        assert_eq!(
+            parse_str(&["--frobnicate"]).unwrap_err(),
+            "unknown option --frobnicate"
+        );
+        assert_eq!(parse_str(&["--file"]).unwrap_err(), "--file needs a value");
+        assert!(parse_str(&["a.txt", "--merge", "b.json"]).is_err());
+        assert!(parse_str(&["--add", "x", "a.txt"]).is_err());
+    }
+}
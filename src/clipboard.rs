@@ -0,0 +1,50 @@
+use crate::app::AppError;
+
+// the system clipboard, opened on the first copy or paste and then kept for as long
+// as the app runs. on X11 and Wayland copied text is only there while the handle
+// that copied it is alive (unless a clipboard manager takes it over). on a headless
+// box or over ssh there's no clipboard and copy/paste return an error
+#[derive(Default)]
+pub struct Clipboard {
+    #[cfg(feature = "clipboard")]
+    handle: Option<arboard::Clipboard>,
+}
+
+#[cfg(feature = "clipboard")]
+impl Clipboard {
+    pub fn copy(&mut self, text: &str) -> Result<(), AppError> {
+        self.handle()?.set_text(text).map_err(clipboard_error)
+    }
+
+    pub fn paste(&mut self) -> Result<String, AppError> {
+        self.handle()?.get_text().map_err(clipboard_error)
+    }
+
+    fn handle(&mut self) -> Result<&mut arboard::Clipboard, AppError> {
+        match &mut self.handle {
+            Some(handle) => Ok(handle),
+            handle => Ok(handle.insert(arboard::Clipboard::new().map_err(clipboard_error)?)),
+        }
+    }
+}
+
+#[cfg(feature = "clipboard")]
+fn clipboard_error(err: arboard::Error) -> AppError {
+    AppError::Clipboard(err.to_string())
+}
+
+#[cfg(not(feature = "clipboard"))]
+impl Clipboard {
+    pub fn copy(&mut self, _text: &str) -> Result<(), AppError> {
+        Err(not_built())
+    }
+
+    pub fn paste(&mut self) -> Result<String, AppError> {
+        Err(not_built())
+    }
+}
+
+#[cfg(not(feature = "clipboard"))]
+fn not_built() -> AppError {
+    AppError::Clipboard("built without the clipboard feature".to_string())
+}
@@ -0,0 +1,44 @@
+use serde::Deserialize;
+use std::{fs, io, path::Path};
+
+use crate::{
+    app::{AppError, Format},
+    symbols::SymbolsConfig,
+};
+
+// settings from config.toml, e.g.
+//
+//   theme = "light"
+//   truncate_todos = true
+//   max_todo_length = 80
+//   format = "toml"
+//   preview_width = 40
+//
+//   [symbols]
+//   selected = "> "
+#[derive(Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    // "dark" (the default) or "light"
+    pub theme: Option<String>,
+    // cut long todos with "…" instead of wrapping them over several lines
+    pub truncate_todos: bool,
+    // longest todo text that can be typed, shown next to the count while adding/editing
+    pub max_todo_length: Option<usize>,
+    // "json" (the default), "toml" or "sqlite", picks todos.json, todos.toml or todos.db
+    pub format: Format,
+    // percent of the width the preview pane takes, 40 by default
+    pub preview_width: Option<u16>,
+    // the markers drawn in front of each todo
+    pub symbols: SymbolsConfig,
+}
+
+impl Config {
+    pub fn load(path: &Path) -> Result<Config, AppError> {
+        match fs::read_to_string(path) {
+            Ok(contents) => Ok(toml::from_str(&contents)?),
+            Err(err) if err.kind() == io::ErrorKind::NotFound => Ok(Config::default()),
+            Err(err) => Err(err.into()),
+        }
+    }
+}
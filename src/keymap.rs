@@ -0,0 +1,209 @@
+use crossterm::event::{KeyCode, KeyModifiers};
+use serde::Deserialize;
+use std::{fs, io, path::Path};
+
+use crate::{
+    action::{self, Action},
+    app::AppError,
+};
+
+// what keys.toml looks like, every entry is optional:
+//
+//   move_up = ["w", "Up"]
+//   move_down = ["m", "Down"]
+#[derive(Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct KeymapFile {
+    move_up: Option<Vec<String>>,
+    move_down: Option<Vec<String>>,
+    add: Option<Vec<String>>,
+    delete: Option<Vec<String>>,
+    edit: Option<Vec<String>>,
+    search: Option<Vec<String>>,
+    quit: Option<Vec<String>>,
+}
+
+pub struct Keymap {
+    bindings: Vec<(KeyCode, Action)>,
+}
+
+impl Default for Keymap {
+    fn default() -> Self {
+        Self {
+            bindings: vec![
+                (KeyCode::Char('k'), Action::MoveUp),
+                (KeyCode::Up, Action::MoveUp),
+                (KeyCode::Char('j'), Action::MoveDown),
+                (KeyCode::Down, Action::MoveDown),
+                (KeyCode::Char('a'), Action::Add),
+                (KeyCode::Char('r'), Action::Delete),
+                (KeyCode::Char('d'), Action::Delete),
+                (KeyCode::Char('i'), Action::Edit),
+                (KeyCode::Char('/'), Action::Search),
+                (KeyCode::Char('q'), Action::Quit),
+                (KeyCode::Esc, Action::Quit),
+            ],
+        }
+    }
+}
+
+impl Keymap {
+    // the defaults, with any action listed in the file replaced by the file's keys.
+    // a key Normal mode already uses for something that can't be rebound is an error
+    pub fn load(path: &Path) -> Result<Keymap, AppError> {
+        let contents = match fs::read_to_string(path) {
+            Ok(contents) => contents,
+            // no config, the defaults it is
+            Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(Keymap::default()),
+            Err(err) => return Err(err.into()),
+        };
+        let file: KeymapFile = toml::from_str(&contents)?;
+
+        let mut keymap = Keymap::default();
+        let overrides = [
+            (Action::MoveUp, file.move_up),
+            (Action::MoveDown, file.move_down),
+            (Action::Add, file.add),
+            (Action::Delete, file.delete),
+            (Action::Edit, file.edit),
+            (Action::Search, file.search),
+            (Action::Quit, file.quit),
+        ];
+        for (action, keys) in overrides {
+            let Some(keys) = keys else {
+                continue;
+            };
+            keymap.bindings.retain(|(_, bound)| *bound != action);
+            for key in keys {
+                let code = parse_key(&key).ok_or(AppError::InvalidKey(key.clone()))?;
+                if action::is_fixed_key(code) {
+                    return Err(AppError::FixedKey(key));
+                }
+                keymap.bindings.push((code, action));
+            }
+        }
+        Ok(keymap)
+    }
+
+    // control/alt combinations are never rebindable, so ctrl+r doesn't trigger `r`
+    pub fn action(&self, code: KeyCode, modifiers: KeyModifiers) -> Option<Action> {
+        if modifiers.intersects(KeyModifiers::CONTROL | KeyModifiers::ALT) {
+            return None;
+        }
+        self.bindings
+            .iter()
+            .find(|(bound, _)| *bound == code)
+            .map(|(_, action)| *action)
+    }
+
+    // e.g. "j/Down", for hints in the UI
+    pub fn label(&self, action: Action) -> String {
+        self.bindings
+            .iter()
+            .filter(|(_, bound)| *bound == action)
+            .map(|(code, _)| key_name(*code))
+            .collect::<Vec<_>>()
+            .join("/")
+    }
+}
+
+fn parse_key(name: &str) -> Option<KeyCode> {
+    let code = match name {
+        "Up" => KeyCode::Up,
+        "Down" => KeyCode::Down,
+        "Left" => KeyCode::Left,
+        "Right" => KeyCode::Right,
+        "Home" => KeyCode::Home,
+        "End" => KeyCode::End,
+        "PageUp" => KeyCode::PageUp,
+        "PageDown" => KeyCode::PageDown,
+        "Enter" => KeyCode::Enter,
+        "Esc" => KeyCode::Esc,
+        "Tab" => KeyCode::Tab,
+        "Backspace" => KeyCode::Backspace,
+        "Delete" => KeyCode::Delete,
+        "Space" => KeyCode::Char(' '),
+        _ => {
+            let mut chars = name.chars();
+            match (chars.next(), chars.next()) {
+                (Some(c), None) => KeyCode::Char(c),
+                _ => return None,
+            }
+        }
+    };
+    Some(code)
+}
+
+pub fn key_name(code: KeyCode) -> String {
+    match code {
+        KeyCode::Char(' ') => "Space".to_string(),
+        KeyCode::Char(c) => c.to_string(),
+        KeyCode::Up => "Up".to_string(),
+        KeyCode::Down => "Down".to_string(),
+        KeyCode::Left => "Left".to_string(),
+        KeyCode::Right => "Right".to_string(),
+        KeyCode::Home => "Home".to_string(),
+        KeyCode::End => "End".to_string(),
+        KeyCode::PageUp => "PageUp".to_string(),
+        KeyCode::PageDown => "PageDown".to_string(),
+        KeyCode::Enter => "Enter".to_string(),
+        KeyCode::Esc => "Esc".to_string(),
+        KeyCode::Tab => "Tab".to_string(),
+        KeyCode::BackTab => "Shift+Tab".to_string(),
+        KeyCode::Backspace => "Backspace".to_string(),
+        KeyCode::Delete => "Delete".to_string(),
+        other => format!("{:?}", other),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn file_entries_replace_the_default_keys() {
+        let path = std::env::temp_dir().join("ratatui-todo-keymap-test.toml");
+        fs::write(&path, "move_down = [\"m\", \"Down\"]\n").unwrap();
+        let keymap = Keymap::load(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+
+        let none = KeyModifiers::NONE;
+        assert_eq!(
+            keymap.action(KeyCode::Char('m'), none),
+            Some(Action::MoveDown)
+        );
+        assert_eq!(keymap.action(KeyCode::Down, none), Some(Action::MoveDown));
+        assert_eq!(keymap.action(KeyCode::Char('j'), none), None);
+        // untouched actions keep their defaults
+        assert_eq!(
+            keymap.action(KeyCode::Char('k'), none),
+            Some(Action::MoveUp)
+        );
+    }
+
+    #[test]
+    fn keys_that_cant_be_rebound_are_refused() {
+        let path = std::env::temp_dir().join("ratatui-todo-fixed-keymap-test.toml");
+        // `t` filters by tag
+        fs::write(&path, "move_up = [\"t\", \"Up\"]\n").unwrap();
+        let result = Keymap::load(&path);
+        fs::remove_file(&path).unwrap();
+        assert!(matches!(result, Err(AppError::FixedKey(key)) if key == "t"));
+
+        // the defaults don't clash with them
+        let keymap = Keymap::default();
+        assert!(!keymap
+            .bindings
+            .iter()
+            .any(|(code, _)| action::is_fixed_key(*code)));
+    }
+
+    #[test]
+    fn control_combinations_are_not_looked_up() {
+        let keymap = Keymap::default();
+        assert_eq!(
+            keymap.action(KeyCode::Char('r'), KeyModifiers::CONTROL),
+            None
+        );
+    }
+}
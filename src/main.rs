@@ -1,204 +1,159 @@
+mod action;
+mod app;
+mod cli;
+mod clipboard;
+mod config;
+mod keymap;
+mod sqlite;
+mod symbols;
+mod theme;
+mod ui;
+
 use crossterm::{
-    event::{
-        self, DisableMouseCapture, EnableMouseCapture, Event, KeyCode, KeyEvent, KeyEventKind,
-    },
+    cursor,
+    event::{self, DisableMouseCapture, EnableMouseCapture, Event, KeyEvent, KeyEventKind},
     execute,
     terminal::{disable_raw_mode, enable_raw_mode, EnterAlternateScreen, LeaveAlternateScreen},
 };
-use std::io;
-
-use ratatui::{
-    backend::CrosstermBackend,
-    layout::{Constraint, Direction, Layout, Rect},
-    style::{Color, Style},
-    text::Line,
-    widgets::{Block, Borders, List, ListItem, Paragraph},
-    Terminal,
+use std::{
+    env, fs,
+    io::{self, IsTerminal},
+    panic,
+    path::{Path, PathBuf},
+    process,
+    time::{Duration, Instant},
 };
 
-enum InputMode {
-    Normal,
-    Editing,
-    Searching,
-    Adding,
-    Confirming,
-}
-impl Default for InputMode {
-    fn default() -> Self {
-        InputMode::Normal // this will be our default mode
-    }
-}
-
-#[derive(Default)]
-struct App {
-    input_mode: InputMode,
-    search_input: String,
-    todos: Vec<String>, // we'll make this more sophisticated later
-    filtered_todos: Vec<String>,
-    selected_index: Option<usize>,
-    // for add
-    input_buffer: String,
-    show_confirmation: bool,
-}
-
-impl App {
-    fn new() -> Self {
-        let todos = vec![
-            "Learn Rust".to_string(),
-            "Build a TUI app".to_string(),
-            "Share with others".to_string(),
-            "Write documentation".to_string(),
-            "Add more features".to_string(),
-        ];
-        let filtered_todos = todos.clone();
-
-        Self {
-            input_mode: InputMode::Normal,
-            search_input: String::new(),
-            todos,
-            filtered_todos,
-            selected_index: Some(0),
-            input_buffer: String::new(),
-            show_confirmation: false,
-        }
-    }
-
-    fn move_selection_up(&mut self) {
-        self.selected_index = match self.selected_index {
-            Some(i) => {
-                if i > 0 {
-                    Some(i - 1)
-                } else {
-                    // go to the bottom
-                    Some(self.filtered_todos.len() - 1)
-                }
-            }
-            None => {
-                if !self.filtered_todos.is_empty() {
-                    Some(0)
-                } else {
-                    None
-                }
+use ratatui::{backend::CrosstermBackend, Terminal};
+
+use app::{App, AppError, Format};
+use cli::Import;
+use config::Config;
+use keymap::Keymap;
+use symbols::Symbols;
+use theme::Theme;
+
+fn main() -> Result<(), AppError> {
+    // read first, it decides which data file gets loaded
+    let config_path = config_dir().join("config.toml");
+    let config = Config::load(&config_path).and_then(|config| {
+        let theme = match &config.theme {
+            Some(name) => {
+                Theme::by_name(name).ok_or_else(|| AppError::UnknownTheme(name.clone()))?
             }
+            None => Theme::default(),
         };
-    }
-
-    fn move_selection_down(&mut self) {
-        let len = self.filtered_todos.len();
-        self.selected_index = match self.selected_index {
-            Some(i) => {
-                if i < len - 1 {
-                    Some(i + 1)
-                } else {
-                    // to go the start
-                    Some(0)
-                }
-            }
-            None => {
-                if !self.filtered_todos.is_empty() {
-                    Some(0)
-                } else {
-                    None
-                }
-            }
+        Ok((config, theme))
+    });
+    let format = match &config {
+        Ok((config, _)) => config.format,
+        Err(_) => Format::default(),
+    };
+
+    let args = match cli::parse(env::args_os().skip(1)) {
+        Ok(args) if args.help => {
+            println!("{}", cli::USAGE);
+            return Ok(());
         }
-    }
-
-    fn filter_todos(&mut self) {
-        if self.search_input.is_empty() {
-            self.filtered_todos = self.todos.clone();
-        } else {
-            let search_term = self.search_input.to_lowercase();
-            self.filtered_todos = self
-                .todos
-                .iter()
-                .filter(|todo| todo.to_lowercase().contains(&search_term))
-                .cloned()
-                .collect();
+        Ok(args) => args,
+        Err(err) => {
+            eprintln!("{}\n\n{}", err, cli::USAGE);
+            process::exit(2);
         }
+    };
+    // `--file` wins over the one config.toml picks
+    let data_path = args.file.unwrap_or_else(|| data_path(format));
 
-        // reset selection if it's now out of bounds
-        // todo: check if its better to reset the selected_index value every time a todo is searched
-        if let Some(selected) = self.selected_index {
-            if selected >= self.filtered_todos.len() {
-                self.selected_index = if self.filtered_todos.is_empty() {
-                    Some(0)
-                } else {
-                    Some(self.filtered_todos.len() - 1)
-                }
-            }
-        }
+    // `todo-app-v1 --add "buy milk"` never opens the TUI
+    if let Some(text) = args.add {
+        add_and_exit(&data_path, text);
     }
 
-    fn add_todo(&mut self) {
-        if !self.input_buffer.is_empty() {
-            self.todos.push(self.input_buffer.clone());
-            self.input_buffer.clear();
-            self.filter_todos(); // refresh filtered list
-        }
-    }
-
-    fn delete_selected_todo(&mut self) {
-        if let Some(selected_index) = self.selected_index {
-            // find the corresponding index in the original todos list
-            if let Some(selected_todo) = self.filtered_todos.get(selected_index) {
-                if let Some(original_index) = self.todos.iter().position(|x| x == selected_todo) {
-                    self.todos.remove(original_index);
-                    self.filter_todos(); // refresh filtered list
-
-                    // adjust selection
-                    if self.filtered_todos.is_empty() {
-                        self.selected_index = None
-                    } else {
-                        self.selected_index =
-                            Some(selected_index.min(self.filtered_todos.len() - 1))
-                    }
-                }
+    // Create app state, before touching the terminal so errors print normally
+    let piped_stdin = !io::stdin().is_terminal();
+    let mut app = match args.import {
+        // `todo-app --merge todos.json` adds the todos that aren't there yet to the
+        // saved list instead of replacing it
+        Some(Import::Merge(path)) => {
+            let mut app = saved_app(&data_path);
+            let result = app::read_import(&path).map(|todos| app.merge_todos(todos));
+            if let Err(err) = result {
+                eprintln!("Could not import {}: {}", path.display(), err);
+                process::exit(1);
             }
+            app
         }
-    }
-
-    fn start_delete_confirmation(&mut self) {
-        if self.selected_index.is_some() {
-            self.input_mode = InputMode::Confirming;
-            self.show_confirmation = true
+        Some(Import::Lines(path)) => {
+            let mut app = saved_app(&data_path);
+            if let Err(err) = app.import_lines(&path) {
+                eprintln!("Could not import {}: {}", path.display(), err);
+                process::exit(1);
+            }
+            app
         }
-    }
-
-    fn cancel_delete(&mut self) {
-        self.input_mode = InputMode::Normal;
-        self.show_confirmation = false;
-    }
-
-    fn start_editing(&mut self) {
-        if let Some(selected_index) = self.selected_index {
-            if let Some(todo) = self.filtered_todos.get(selected_index) {
-                self.input_buffer = todo.clone();
-                self.input_mode = InputMode::Editing;
+        // `todo-app tasks.txt` starts from the lines of tasks.txt
+        Some(Import::Open(path)) => {
+            if piped_stdin {
+                eprintln!(
+                    "Both a file and piped input were given, using {}",
+                    path.display()
+                );
+            }
+            match fs::read_to_string(&path) {
+                Ok(contents) => imported_app(&contents),
+                Err(err) => {
+                    eprintln!("Could not read {}: {}", path.display(), err);
+                    process::exit(1);
+                }
             }
         }
+        // `cat tasks.txt | todo-app`. crossterm reads key events from /dev/tty
+        // when stdin isn't a terminal, so the TUI still works afterwards
+        None if piped_stdin => match io::read_to_string(io::stdin()) {
+            Ok(contents) => imported_app(&contents),
+            Err(err) => {
+                eprintln!("Could not read todos from stdin: {}", err);
+                process::exit(1);
+            }
+        },
+        None => saved_app(&data_path),
+    };
+    if let Some(term) = args.search {
+        app.search_input = term;
+        app.filter_todos();
     }
 
-    fn save_edit(&mut self) {
-        if let Some(selected_index) = self.selected_index {
-            if let Some(selected_todo) = self.filtered_todos.get(selected_index) {
-                if let Some(original_index) = self.todos.iter().position(|x| x == selected_todo) {
-                    if !self.input_buffer.is_empty() {
-                        self.todos[original_index] = self.input_buffer.clone();
-                        self.filter_todos();
-                    }
-                }
-            }
+    // a panic would otherwise leave the shell in raw mode on the alternate screen,
+    // so restore the terminal before the panic message gets printed
+    let default_hook = panic::take_hook();
+    panic::set_hook(Box::new(move |info| {
+        let _ = restore_terminal();
+        default_hook(info);
+    }));
+
+    match config {
+        Ok((config, theme)) => {
+            app.theme = theme;
+            app.truncate_todos = config.truncate_todos;
+            app.max_len = config.max_todo_length;
+            app.preview_width = config.preview_width;
+            app.symbols = Symbols::from_config(&config.symbols, symbols::utf8_locale());
         }
+        Err(err) => app.set_error(&format!(
+            "Could not load {}: {}",
+            config_path.display(),
+            err
+        )),
     }
+    app.theme = app.theme.for_terminal();
 
-    fn cancel_edit(&mut self) {
-        self.input_buffer.clear();
-        self.input_mode = InputMode::Normal;
+    let keys_path = config_dir().join("keys.toml");
+    match Keymap::load(&keys_path) {
+        Ok(keymap) => app.keymap = keymap,
+        // fall back to the default keys, but say why
+        Err(err) => app.set_error(&format!("Could not load {}: {}", keys_path.display(), err)),
     }
-}
 
-fn main() -> Result<(), io::Error> {
     // setup terminal
     enable_raw_mode()?;
 
@@ -207,258 +162,148 @@ fn main() -> Result<(), io::Error> {
     let backend = CrosstermBackend::new(stdout);
     let mut terminal = Terminal::new(backend)?;
 
-    // Create app state
-
     // Run the application
-    let res = run_app(&mut terminal);
+    let res = run_app(&mut terminal, app);
 
     // restore terminal
-    disable_raw_mode()?;
-    execute!(
-        terminal.backend_mut(),
-        LeaveAlternateScreen,
-        DisableMouseCapture,
-    )?;
+    restore_terminal()?;
     terminal.show_cursor()?;
 
-    if let Err(err) = res {
-        println!("{:?}", err)
+    // printed after leaving the alternate screen so it stays in the shell, e.g.
+    // `todo-app-v1 && echo saved` only echoes when everything made it to disk
+    match res {
+        Ok(app) => {
+            println!("{}", app.exit_summary());
+            if app.save_failed {
+                eprintln!("The latest changes couldn't be saved");
+                process::exit(1);
+            }
+        }
+        Err(err) => {
+            eprintln!("{}", err);
+            process::exit(1);
+        }
     }
 
     Ok(())
 }
 
-fn run_app<B: ratatui::backend::Backend>(terminal: &mut Terminal<B>) -> io::Result<()> {
-    let mut app = App::new();
-    loop {
-        terminal.draw(|frame| {
-            let main_layout = Layout::default()
-                .direction(Direction::Vertical)
-                .constraints([
-                    Constraint::Length(3),
-                    Constraint::Min(1),
-                    Constraint::Length(3),
-                ])
-                .split(frame.area());
-
-            // =========== Render input area (search or add input) ================ //
-            let input_text = match app.input_mode {
-                InputMode::Searching => format!("Search: {}", app.search_input),
-                InputMode::Adding => format!("New todo: {}", app.input_buffer),
-                InputMode::Editing => format!("Edit todo: {}", app.input_buffer),
-                _ => format!("Press '/' to search (Filter: {})", app.search_input),
-            };
-
-            let input_block_title = match app.input_mode {
-                InputMode::Adding => "Add todo",
-                InputMode::Editing => "Edit todo",
-                _ => "Search",
-            };
+fn restore_terminal() -> io::Result<()> {
+    disable_raw_mode()?;
+    execute!(
+        io::stdout(),
+        LeaveAlternateScreen,
+        DisableMouseCapture,
+        cursor::Show
+    )
+}
 
-            let input_area = Paragraph::new(Line::from(input_text))
-                .style(Style::default())
-                .block(
-                    Block::default()
-                        .title(input_block_title)
-                        .borders(Borders::ALL),
-                );
+// loads the saved todos, adds one and saves them again, all without the TUI
+fn add_and_exit(path: &Path, text: String) -> ! {
+    // a file that can't be loaded isn't overwritten with just the new todo
+    let mut app = match App::load(path) {
+        Ok(app) => app,
+        Err(err) => {
+            eprintln!("Could not load {}: {}", path.display(), err);
+            process::exit(1);
+        }
+    };
+    app.input_buffer = text;
+    let count = app.todos.len();
+    app.add_todo();
+    if app.todos.len() == count {
+        eprintln!("Nothing to add");
+        process::exit(1);
+    }
+    if let Err(err) = app.save(path) {
+        eprintln!("Could not save {}: {}", path.display(), err);
+        process::exit(1);
+    }
+    println!("Added \"{}\"", app.todos[count].text);
+    process::exit(0);
+}
 
-            frame.render_widget(input_area, main_layout[0]);
+// the todos saved last time, or an empty app saying why they couldn't be loaded
+fn saved_app(path: &Path) -> App {
+    match App::load(path) {
+        Ok(app) => app,
+        Err(err) => {
+            let mut app = App::default();
+            app.set_error(&format!("Could not load {}: {}", path.display(), err));
+            app
+        }
+    }
+}
 
-            // render the todo list with selection highlight
-            let todos: Vec<ListItem> = app
-                .filtered_todos
-                .iter()
-                .enumerate()
-                .map(|(i, todo)| {
-                    let style = if Some(i) == app.selected_index {
-                        Style::default().fg(Color::Blue)
-                    } else {
-                        Style::default()
-                    };
-                    let symbol = if Some(i) == app.selected_index {
-                        "-> "
-                    } else {
-                        "- "
-                    };
-                    let todo_str = format!("{}{}", symbol, todo.as_str());
-                    ListItem::new(Line::from(todo_str)).style(style)
-                })
-                .collect();
+// one todo per line of `contents`. it isn't saved, so looking at a text file can't
+// overwrite the saved todos, `--import` adds the lines to them instead
+fn imported_app(contents: &str) -> App {
+    let mut app = App::from_lines(contents.lines().map(str::to_string));
+    app.set_message("Not saved, use --import to add these to the saved todos");
+    app
+}
 
-            let todos_list = List::new(todos)
-                .block(
-                    Block::default()
-                        .title(format!("Todos ({} shown)", app.filtered_todos.len()))
-                        .borders(Borders::ALL),
-                )
-                .style(Style::default());
+// ~/.config/ratatui-todo (or $XDG_CONFIG_HOME/ratatui-todo)
+fn config_dir() -> PathBuf {
+    env::var_os("XDG_CONFIG_HOME")
+        .map(PathBuf::from)
+        .or_else(|| env::var_os("HOME").map(|home| PathBuf::from(home).join(".config")))
+        .unwrap_or_else(|| PathBuf::from("."))
+        .join("ratatui-todo")
+}
 
-            frame.render_widget(todos_list, main_layout[1]);
+// todos.json, or todos.toml/todos.db depending on `format` in config.toml
+fn data_path(format: Format) -> PathBuf {
+    config_dir().join(format!("todos.{}", format.extension()))
+}
 
-            // update status bar to show search instructions
-            let mode_text = match app.input_mode {
-                InputMode::Normal => {
-                    "Normal Mode | q/esc: quit, /: search, a: add, i: edit, r/d: remove, j/k: move"
-                }
-                InputMode::Searching => "Search Mode | Enter: apply filter, Esc: clear filter",
-                InputMode::Adding => "Add Mode | Enter: save todo, Esc: cancel",
-                InputMode::Confirming => "Delete? | y: continue, n/Esc: cancel",
-                InputMode::Editing => "Edit Mode | Enter: save changes, Esc: cancel",
-            };
-            let status_bar = Paragraph::new(Line::from(mode_text))
-                .style(Style::default())
-                .block(Block::default().title("Status").borders(Borders::ALL));
+// how often the screen is redrawn when no events come in
+const TICK_RATE: Duration = Duration::from_millis(250);
 
-            frame.render_widget(status_bar, main_layout[2]);
+fn run_app<B: ratatui::backend::Backend>(
+    terminal: &mut Terminal<B>,
+    mut app: App,
+) -> Result<App, AppError> {
+    loop {
+        let mut drawn = None;
+        terminal.draw(|frame| drawn = Some(ui::ui(frame, &app)))?;
+        if let Some(drawn) = drawn {
+            drawn.apply(&mut app);
+        }
 
-            // render configuration dialog if needed
-            if app.show_confirmation {
-                // create a temporal string that lives long enough to be used in the Line::from function
-                let fallback_string = String::new();
-                let selected_todo = app
-                    .selected_index
-                    .and_then(|i| app.filtered_todos.get(i))
-                    .unwrap_or(&fallback_string);
+        if app.should_quit {
+            return Ok(app);
+        }
 
-                let popup_area = centered_rect(60, 30, frame.area());
-                let confirmation = Paragraph::new(vec![
-                    Line::from("Delete this todo?"),
-                    Line::from(""),
-                    Line::from(selected_todo.as_str()),
-                    Line::from(""),
-                    Line::from("Press 'y' to confirm or 'n'/Esc to cancel"),
-                ])
-                .alignment(ratatui::layout::Alignment::Center)
-                .block(
-                    Block::default()
-                        .title("Confirm delete")
-                        .borders(Borders::ALL),
-                );
+        app.maybe_autosave(Instant::now());
+        app.expire_message(Instant::now());
 
-                frame.render_widget(
-                    Block::default()
-                        .style(Style::default().bg(Color::Black))
-                        .borders(Borders::ALL),
-                    frame.area(),
-                );
-                frame.render_widget(confirmation, popup_area);
-            }
-        })?;
+        // wake up every so often even without input, so the clock keeps moving and
+        // pending changes get saved
+        if !event::poll(TICK_RATE)? {
+            continue;
+        }
 
         // handle events
+        let event = event::read()?;
+        if let Event::Resize(_, _) = event {
+            // start the next frame from a blank screen so nothing from the old size lingers
+            terminal.clear()?;
+        }
+        if let Event::Mouse(mouse) = event {
+            app.handle_mouse(mouse);
+        }
         if let Event::Key(KeyEvent {
             code,
+            modifiers,
             kind: KeyEventKind::Press,
             ..
-        }) = event::read()?
+        }) = event
         {
-            match app.input_mode {
-                InputMode::Normal => match code {
-                    KeyCode::Char('q') | KeyCode::Esc => return Ok(()),
-                    KeyCode::Char('/') => {
-                        app.input_mode = InputMode::Searching;
-                        // app.search_input.clear();
-                    }
-                    KeyCode::Char('a') => {
-                        app.input_mode = InputMode::Adding;
-                        app.input_buffer.clear();
-                    }
-                    KeyCode::Char('r') | KeyCode::Char('d') => {
-                        app.start_delete_confirmation();
-                    }
-                    KeyCode::Char('i') => {
-                        if app.selected_index.is_some() {
-                            app.start_editing();
-                        }
-                    }
-                    KeyCode::Char('j') | KeyCode::Down => app.move_selection_down(),
-                    KeyCode::Char('k') | KeyCode::Up => app.move_selection_up(),
-                    _ => {}
-                },
-                InputMode::Searching => match code {
-                    KeyCode::Enter => app.input_mode = InputMode::Normal,
-                    KeyCode::Esc => {
-                        app.input_mode = InputMode::Normal;
-                        app.search_input.clear();
-                        // it will reset because the search input is now cleared (empty)
-                        app.filter_todos(); // reset to show all todos
-                    }
-                    KeyCode::Char(c) => {
-                        app.search_input.push(c);
-                        app.filter_todos(); // update filtered todos on each keystroke
-                    }
-                    KeyCode::Backspace => {
-                        app.search_input.pop();
-                        app.filter_todos(); // update filtered todos on backspace
-                    }
-                    _ => {}
-                },
-                InputMode::Adding => match code {
-                    KeyCode::Enter => {
-                        app.add_todo();
-                        app.input_mode = InputMode::Normal;
-                    }
-                    KeyCode::Esc => {
-                        app.input_mode = InputMode::Normal;
-                        app.input_buffer.clear();
-                    }
-                    KeyCode::Char(c) => {
-                        app.input_buffer.push(c);
-                    }
-                    KeyCode::Backspace => {
-                        app.input_buffer.pop();
-                    }
-                    _ => {}
-                },
-                InputMode::Confirming => match code {
-                    KeyCode::Char('y') => {
-                        app.delete_selected_todo();
-                        app.cancel_delete();
-                    }
-                    KeyCode::Char('n') | KeyCode::Esc => {
-                        app.cancel_delete();
-                    }
-                    _ => {}
-                },
-                InputMode::Editing => match code {
-                    KeyCode::Enter => {
-                        app.save_edit();
-                    }
-                    KeyCode::Esc => {
-                        app.cancel_edit();
-                    }
-                    KeyCode::Char(c) => {
-                        app.input_buffer.push(c);
-                    }
-                    KeyCode::Backspace => {
-                        app.input_buffer.pop();
-                    }
-                    _ => {}
-                },
+            app.handle_key(code, modifiers);
+            if app.should_quit {
+                return Ok(app);
             }
         }
     }
 }
-
-// helper function to create a centered rect using percentage of the available area
-fn centered_rect(percent_x: u16, percent_y: u16, r: Rect) -> Rect {
-    let popup_layout = Layout::default()
-        .direction(Direction::Vertical)
-        .constraints([
-            Constraint::Percentage((100 - percent_y) / 2),
-            Constraint::Percentage(percent_y),
-            Constraint::Percentage((100 - percent_y) / 2),
-        ])
-        .split(r);
-
-    // then cut the middle vertical piece into three horizontal pieces
-    Layout::default()
-        .direction(Direction::Horizontal)
-        .constraints([
-            Constraint::Percentage((100 - percent_y) / 2),
-            Constraint::Percentage(percent_x),
-            Constraint::Percentage((100 - percent_x) / 2),
-        ])
-        .split(popup_layout[1])[1] // return the middle chunk
-}
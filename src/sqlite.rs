@@ -0,0 +1,235 @@
+use std::{collections::HashMap, path::Path};
+
+#[cfg(feature = "sqlite")]
+use std::{fs, mem, path::PathBuf};
+
+#[cfg(feature = "sqlite")]
+use rusqlite::{params, Connection, OptionalExtension};
+
+use crate::app::{AppError, Todo};
+
+// the top level todos of each list or the archive, by the name of the place
+pub type Places = HashMap<String, Vec<Todo>>;
+
+// a todo as stored in the todos table: where it is and the todo itself as json,
+// minus its subtasks which get rows of their own
+#[cfg(feature = "sqlite")]
+#[derive(PartialEq)]
+struct Row {
+    place: String,
+    parent: Option<u64>,
+    position: usize,
+    data: String,
+}
+
+// for big lists, a save only writes the todos that changed instead of the whole file
+#[cfg(feature = "sqlite")]
+pub struct Store {
+    path: PathBuf,
+    connection: Connection,
+    // what's in the database right now, to tell which rows a save has to touch
+    saved: HashMap<u64, Row>,
+}
+
+#[cfg(feature = "sqlite")]
+impl Store {
+    pub fn open(path: &Path) -> Result<Store, AppError> {
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        let connection = Connection::open(path).map_err(sqlite_error)?;
+        connection
+            .execute_batch(
+                "CREATE TABLE IF NOT EXISTS todos (
+                    id INTEGER PRIMARY KEY,
+                    place TEXT NOT NULL,
+                    parent INTEGER,
+                    position INTEGER NOT NULL,
+                    data TEXT NOT NULL
+                );
+                CREATE TABLE IF NOT EXISTS state (
+                    id INTEGER PRIMARY KEY CHECK (id = 0),
+                    data TEXT NOT NULL
+                );",
+            )
+            .map_err(sqlite_error)?;
+        Ok(Store {
+            path: path.to_path_buf(),
+            connection,
+            saved: HashMap::new(),
+        })
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    // the saved state and the todos of each place (a list or the archive), `None`
+    // for a database nothing was saved to yet
+    pub fn load(&mut self) -> Result<Option<(String, Places)>, AppError> {
+        let state: Option<String> = self
+            .connection
+            .query_row("SELECT data FROM state WHERE id = 0", [], |row| row.get(0))
+            .optional()
+            .map_err(sqlite_error)?;
+        let Some(state) = state else {
+            return Ok(None);
+        };
+
+        let mut statement = self
+            .connection
+            .prepare("SELECT id, place, parent, position, data FROM todos ORDER BY position")
+            .map_err(sqlite_error)?;
+        let rows = statement
+            .query_map([], |row| {
+                Ok((
+                    row.get::<_, i64>(0)? as u64,
+                    Row {
+                        place: row.get(1)?,
+                        parent: row.get::<_, Option<i64>>(2)?.map(|id| id as u64),
+                        position: row.get::<_, i64>(3)? as usize,
+                        data: row.get(4)?,
+                    },
+                ))
+            })
+            .map_err(sqlite_error)?
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(sqlite_error)?;
+
+        // rows come sorted by position, so pushing keeps every list in order
+        let mut todos = HashMap::new();
+        let mut children: HashMap<u64, Vec<u64>> = HashMap::new();
+        let mut top_level: Vec<(String, u64)> = Vec::new();
+        for (id, row) in &rows {
+            todos.insert(*id, serde_json::from_str::<Todo>(&row.data)?);
+            match row.parent {
+                Some(parent) => children.entry(parent).or_default().push(*id),
+                None => top_level.push((row.place.clone(), *id)),
+            }
+        }
+        let mut places = Places::new();
+        for (place, id) in top_level {
+            if let Some(todo) = build_tree(id, &mut todos, &children) {
+                places.entry(place).or_default().push(todo);
+            }
+        }
+
+        self.saved = rows.into_iter().collect();
+        Ok(Some((state, places)))
+    }
+
+    // writes the todos that were added, changed or moved since the last save and
+    // deletes the ones that are gone, all in one transaction
+    pub fn save(&mut self, state: &str, places: &[(String, &[Todo])]) -> Result<(), AppError> {
+        let mut rows = HashMap::new();
+        for (place, todos) in places {
+            collect_rows(place, None, todos, &mut rows)?;
+        }
+
+        let transaction = self.connection.transaction().map_err(sqlite_error)?;
+        for (id, row) in &rows {
+            if self.saved.get(id) == Some(row) {
+                continue;
+            }
+            transaction
+                .execute(
+                    "INSERT OR REPLACE INTO todos (id, place, parent, position, data)
+                     VALUES (?1, ?2, ?3, ?4, ?5)",
+                    params![
+                        *id as i64,
+                        row.place,
+                        row.parent.map(|id| id as i64),
+                        row.position as i64,
+                        row.data
+                    ],
+                )
+                .map_err(sqlite_error)?;
+        }
+        for id in self.saved.keys().filter(|id| !rows.contains_key(id)) {
+            transaction
+                .execute("DELETE FROM todos WHERE id = ?1", params![*id as i64])
+                .map_err(sqlite_error)?;
+        }
+        transaction
+            .execute(
+                "INSERT OR REPLACE INTO state (id, data) VALUES (0, ?1)",
+                params![state],
+            )
+            .map_err(sqlite_error)?;
+        transaction.commit().map_err(sqlite_error)?;
+
+        self.saved = rows;
+        Ok(())
+    }
+}
+
+#[cfg(feature = "sqlite")]
+fn collect_rows(
+    place: &str,
+    parent: Option<u64>,
+    todos: &[Todo],
+    rows: &mut HashMap<u64, Row>,
+) -> Result<(), AppError> {
+    for (position, todo) in todos.iter().enumerate() {
+        let mut todo = todo.clone();
+        let children = mem::take(&mut todo.children);
+        let data = serde_json::to_string(&todo).map_err(AppError::Serialize)?;
+        rows.insert(
+            todo.id,
+            Row {
+                place: place.to_string(),
+                parent,
+                position,
+                data,
+            },
+        );
+        collect_rows(place, Some(todo.id), &children, rows)?;
+    }
+    Ok(())
+}
+
+#[cfg(feature = "sqlite")]
+fn build_tree(
+    id: u64,
+    todos: &mut HashMap<u64, Todo>,
+    children: &HashMap<u64, Vec<u64>>,
+) -> Option<Todo> {
+    let mut todo = todos.remove(&id)?;
+    todo.children = children
+        .get(&id)
+        .into_iter()
+        .flatten()
+        .filter_map(|child| build_tree(*child, todos, children))
+        .collect();
+    Some(todo)
+}
+
+#[cfg(feature = "sqlite")]
+fn sqlite_error(err: rusqlite::Error) -> AppError {
+    AppError::Sqlite(err.to_string())
+}
+
+// without the feature a store can't be opened, so there's never one to use
+#[cfg(not(feature = "sqlite"))]
+pub enum Store {}
+
+#[cfg(not(feature = "sqlite"))]
+impl Store {
+    pub fn open(_path: &Path) -> Result<Store, AppError> {
+        Err(AppError::Sqlite(
+            "built without the sqlite feature".to_string(),
+        ))
+    }
+
+    pub fn path(&self) -> &Path {
+        match *self {}
+    }
+
+    pub fn load(&mut self) -> Result<Option<(String, Places)>, AppError> {
+        match *self {}
+    }
+
+    pub fn save(&mut self, _state: &str, _places: &[(String, &[Todo])]) -> Result<(), AppError> {
+        match *self {}
+    }
+}
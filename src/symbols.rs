@@ -0,0 +1,150 @@
+use ratatui::text::Span;
+use serde::Deserialize;
+use std::env;
+
+// the `[symbols]` table of config.toml, every entry is optional:
+//
+//   [symbols]
+//   selected = "> "
+//   unselected = "- "
+//   done = "✔ "
+//   not_done = "· "
+#[derive(Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct SymbolsConfig {
+    selected: Option<String>,
+    unselected: Option<String>,
+    marked: Option<String>,
+    done: Option<String>,
+    not_done: Option<String>,
+    collapsed: Option<String>,
+    expanded: Option<String>,
+}
+
+// what's drawn in front of each todo
+#[derive(Clone)]
+pub struct Symbols {
+    // the pointer on the selected todo, and what the others get in its place
+    pub selected: String,
+    pub unselected: String,
+    // marked with 'v', unmarked is the same width in spaces
+    pub marked: String,
+    pub unmarked: String,
+    pub done: String,
+    pub not_done: String,
+    // the arrow in front of todos with subtasks
+    pub collapsed: String,
+    pub expanded: String,
+    // the subtask gauge and the end of cut off text, not configurable
+    pub bar_filled: String,
+    pub bar_empty: String,
+    pub ellipsis: String,
+}
+
+impl Default for Symbols {
+    fn default() -> Self {
+        Symbols::from_config(&SymbolsConfig::default(), true)
+    }
+}
+
+impl Symbols {
+    // a symbol the terminal can't show (non-ascii without a utf-8 locale) falls back to
+    // the ascii default, and each pair is padded to the same width so the columns line up
+    pub fn from_config(config: &SymbolsConfig, utf8: bool) -> Self {
+        let pick = |configured: &Option<String>, default: &str, ascii: &str| {
+            let symbol = configured.as_deref().unwrap_or(default);
+            if utf8 || symbol.is_ascii() {
+                symbol.to_string()
+            } else {
+                ascii.to_string()
+            }
+        };
+        let (selected, unselected) = same_width(
+            pick(&config.selected, "-> ", "-> "),
+            pick(&config.unselected, "", ""),
+        );
+        let marked = pick(&config.marked, "✓ ", "* ");
+        let unmarked = " ".repeat(width(&marked));
+        let (done, not_done) = same_width(
+            pick(&config.done, "[x] ", "[x] "),
+            pick(&config.not_done, "[ ] ", "[ ] "),
+        );
+        let (collapsed, expanded) = same_width(
+            pick(&config.collapsed, "▸ ", "> "),
+            pick(&config.expanded, "▾ ", "v "),
+        );
+        let glyph = |unicode: &str, ascii: &str| if utf8 { unicode } else { ascii }.to_string();
+        Self {
+            selected,
+            unselected,
+            marked,
+            unmarked,
+            done,
+            not_done,
+            collapsed,
+            expanded,
+            bar_filled: glyph("■", "#"),
+            bar_empty: glyph("□", "-"),
+            ellipsis: glyph("…", "..."),
+        }
+    }
+}
+
+// whether the locale says the terminal takes utf-8, like most do nowadays
+pub fn utf8_locale() -> bool {
+    ["LC_ALL", "LC_CTYPE", "LANG"]
+        .iter()
+        .filter_map(|name| env::var(name).ok())
+        .find(|value| !value.is_empty())
+        .is_none_or(|value| {
+            let value = value.to_lowercase();
+            value.contains("utf-8") || value.contains("utf8")
+        })
+}
+
+// columns the symbol takes on screen, wide emoji count as two
+pub fn width(symbol: &str) -> usize {
+    Span::raw(symbol).width()
+}
+
+fn same_width(a: String, b: String) -> (String, String) {
+    let target = width(&a).max(width(&b));
+    let pad = |symbol: String| {
+        let padding = target - width(&symbol);
+        symbol + &" ".repeat(padding)
+    };
+    (pad(a), pad(b))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn pairs_are_padded_to_the_same_width() {
+        let config = SymbolsConfig {
+            done: Some("✅".to_string()),
+            not_done: Some("-".to_string()),
+            ..Default::default()
+        };
+        let symbols = Symbols::from_config(&config, true);
+        assert_eq!(symbols.done, "✅");
+        assert_eq!(symbols.not_done, "- ");
+        assert_eq!(width(&symbols.unmarked), width(&symbols.marked));
+        // nothing in front of the other todos by default, just the pointer's width
+        assert_eq!(symbols.unselected, "   ");
+    }
+
+    #[test]
+    fn non_ascii_symbols_fall_back_without_utf8() {
+        let config = SymbolsConfig {
+            selected: Some("➜ ".to_string()),
+            ..Default::default()
+        };
+        let symbols = Symbols::from_config(&config, false);
+        assert_eq!(symbols.selected, "-> ");
+        assert_eq!(symbols.marked, "* ");
+        assert_eq!(symbols.collapsed, "> ");
+        assert_eq!(symbols.ellipsis, "...");
+    }
+}
@@ -0,0 +1,197 @@
+use std::env;
+
+use ratatui::style::{Color, Modifier, Style};
+
+use crate::app::Priority;
+
+#[derive(Clone, Copy)]
+pub struct Theme {
+    pub name: &'static str,
+    pub selection: Color,
+    pub border: Color,
+    pub completed: Color,
+    pub low_priority: Color,
+    pub medium_priority: Color,
+    pub high_priority: Color,
+    pub tag: Color,
+    // background (and text) of the search matches
+    pub match_bg: Color,
+    pub match_fg: Color,
+    // dims everything behind a popup
+    pub popup_backdrop: Color,
+    pub error: Color,
+    pub info: Color,
+    // NO_COLOR is set: every style is the terminal's default
+    pub monochrome: bool,
+    // the terminal takes rgb colors, otherwise they're swapped for the closest of the 256
+    pub truecolor: bool,
+}
+
+impl Default for Theme {
+    fn default() -> Self {
+        Theme::dark()
+    }
+}
+
+impl Theme {
+    pub fn dark() -> Self {
+        Self {
+            name: "dark",
+            selection: Color::Blue,
+            border: Color::Reset,
+            completed: Color::DarkGray,
+            low_priority: Color::Green,
+            medium_priority: Color::Yellow,
+            high_priority: Color::Red,
+            tag: Color::Magenta,
+            match_bg: Color::Yellow,
+            match_fg: Color::Black,
+            popup_backdrop: Color::Black,
+            error: Color::Red,
+            info: Color::Green,
+            monochrome: false,
+            truecolor: true,
+        }
+    }
+
+    pub fn light() -> Self {
+        Self {
+            name: "light",
+            selection: Color::Blue,
+            border: Color::DarkGray,
+            completed: Color::Gray,
+            low_priority: Color::Green,
+            // plain yellow is unreadable on a light background
+            medium_priority: Color::Rgb(175, 95, 0),
+            high_priority: Color::Red,
+            tag: Color::Magenta,
+            match_bg: Color::LightYellow,
+            match_fg: Color::Black,
+            popup_backdrop: Color::White,
+            error: Color::Red,
+            info: Color::Green,
+            monochrome: false,
+            truecolor: true,
+        }
+    }
+
+    pub fn by_name(name: &str) -> Option<Self> {
+        match name {
+            "dark" => Some(Theme::dark()),
+            "light" => Some(Theme::light()),
+            _ => None,
+        }
+    }
+
+    // switches between the two presets
+    pub fn toggled(self) -> Self {
+        let theme = if self.name == "dark" {
+            Theme::light()
+        } else {
+            Theme::dark()
+        };
+        theme.with_color_support(self.monochrome, self.truecolor)
+    }
+
+    // what the environment says the terminal can show, see https://no-color.org
+    pub fn for_terminal(self) -> Self {
+        let no_color = env::var("NO_COLOR").is_ok_and(|value| !value.is_empty());
+        let truecolor =
+            env::var("COLORTERM").is_ok_and(|value| value == "truecolor" || value == "24bit");
+        self.with_color_support(no_color, truecolor)
+    }
+
+    pub fn with_color_support(mut self, monochrome: bool, truecolor: bool) -> Self {
+        self.monochrome = monochrome;
+        self.truecolor = truecolor;
+        if !truecolor {
+            for color in [
+                &mut self.selection,
+                &mut self.border,
+                &mut self.completed,
+                &mut self.low_priority,
+                &mut self.medium_priority,
+                &mut self.high_priority,
+                &mut self.tag,
+                &mut self.match_bg,
+                &mut self.match_fg,
+                &mut self.popup_backdrop,
+                &mut self.error,
+                &mut self.info,
+            ] {
+                *color = indexed(*color);
+            }
+        }
+        self
+    }
+
+    // every colored style in the ui goes through these, so monochrome turns them all off
+    pub fn fg(&self, color: Color) -> Style {
+        if self.monochrome {
+            Style::default()
+        } else {
+            Style::default().fg(color)
+        }
+    }
+
+    pub fn bg(&self, color: Color) -> Style {
+        if self.monochrome {
+            Style::default()
+        } else {
+            Style::default().bg(color)
+        }
+    }
+
+    // search matches still need to stand out without colors
+    pub fn match_style(&self) -> Style {
+        if self.monochrome {
+            Style::default().add_modifier(Modifier::REVERSED)
+        } else {
+            Style::default().fg(self.match_fg).bg(self.match_bg)
+        }
+    }
+
+    pub fn priority(&self, priority: Priority) -> Color {
+        match priority {
+            Priority::Low => self.low_priority,
+            Priority::Medium => self.medium_priority,
+            Priority::High => self.high_priority,
+        }
+    }
+}
+
+// the closest color of the xterm 256 color cube
+fn indexed(color: Color) -> Color {
+    let Color::Rgb(r, g, b) = color else {
+        return color;
+    };
+    let level = |value: u8| match value {
+        0..=47 => 0,
+        48..=114 => 1,
+        _ => (value - 35) / 40,
+    };
+    Color::Indexed(16 + 36 * level(r) + 6 * level(g) + level(b))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn monochrome_styles_have_no_colors() {
+        let theme = Theme::light().with_color_support(true, true);
+        assert_eq!(theme.fg(theme.high_priority), Style::default());
+        assert_eq!(theme.bg(theme.popup_backdrop), Style::default());
+        assert_eq!(theme.match_style().fg, None);
+        // and it stays that way when switching themes
+        assert!(theme.toggled().monochrome);
+    }
+
+    #[test]
+    fn rgb_colors_fall_back_without_truecolor() {
+        let theme = Theme::light().with_color_support(false, false);
+        assert_eq!(theme.medium_priority, Color::Indexed(130));
+        assert_eq!(theme.high_priority, Color::Red);
+        assert_eq!(indexed(Color::Rgb(255, 255, 255)), Color::Indexed(231));
+    }
+}
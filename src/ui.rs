@@ -0,0 +1,1236 @@
+use chrono::Local;
+use ratatui::{
+    layout::{Constraint, Direction, Layout, Margin, Rect},
+    style::{Modifier, Style},
+    text::{Line, Span},
+    widgets::{
+        Block, Borders, Clear, Gauge, List, ListItem, Paragraph, Scrollbar, ScrollbarOrientation,
+        ScrollbarState, Tabs, Wrap,
+    },
+    Frame,
+};
+use std::{mem, ops::Range};
+use unicode_width::{UnicodeWidthChar, UnicodeWidthStr};
+
+use crate::{
+    action::{self, Action},
+    app::{
+        fuzzy_match, match_ranges, AgeFilter, App, InputMode, SearchMode, Severity, StatusFilter,
+        Todo, View,
+    },
+    keymap::Keymap,
+    symbols::{self, Symbols},
+    theme::Theme,
+};
+
+// smaller than this and the UI is replaced by a "too small" message
+const MIN_WIDTH: u16 = 20;
+const MIN_HEIGHT: u16 = 12;
+
+// the preview pane's share of the width unless config.toml says otherwise, and the
+// narrowest list area that still gets split for it
+const PREVIEW_WIDTH: u16 = 40;
+const MIN_PREVIEW_SPLIT_WIDTH: u16 = 60;
+
+// what a draw found out about the layout, the event handling needs it for mouse
+// clicks, paging and scrolling
+pub struct Drawn {
+    pub list_area: Rect,
+    // rows each shown todo took, long ones wrap over several
+    pub item_heights: Vec<usize>,
+    pub list_offset: usize,
+    // clamped so the help can't scroll past its last line
+    pub help_scroll: u16,
+}
+
+impl Drawn {
+    pub fn apply(self, app: &mut App) {
+        app.list_area = self.list_area;
+        app.item_heights = self.item_heights;
+        *app.list_state.offset_mut() = self.list_offset;
+        app.help_scroll = self.help_scroll;
+    }
+}
+
+// draws one frame without touching the app, what it learned about the layout is
+// handed back to be applied afterwards
+pub fn ui(frame: &mut Frame, app: &App) -> Drawn {
+    let theme = app.theme;
+    let border_style = theme.fg(theme.border);
+    let mut drawn = Drawn {
+        list_area: Rect::default(),
+        item_heights: app.item_heights.clone(),
+        list_offset: app.list_state.offset(),
+        help_scroll: app.help_scroll,
+    };
+
+    // the tabs, the gauge and the three blocks need at least 3 rows each, below that
+    // just say so
+    let area = frame.area();
+    if area.width < MIN_WIDTH || area.height < MIN_HEIGHT {
+        frame.render_widget(
+            Paragraph::new("Terminal too small, please resize").wrap(Wrap { trim: true }),
+            area,
+        );
+        return drawn;
+    }
+
+    let main_layout = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints([
+            Constraint::Length(1),
+            Constraint::Length(3),
+            Constraint::Min(1),
+            Constraint::Length(1),
+            Constraint::Length(3),
+        ])
+        .split(frame.area());
+
+    // =========== Render the list tabs ================ //
+    let tabs = Tabs::new(app.list_names())
+        .select(app.active_list)
+        .style(theme.fg(theme.border))
+        .highlight_style(theme.fg(theme.selection).add_modifier(Modifier::BOLD));
+    frame.render_widget(tabs, main_layout[0]);
+    let main_layout = &main_layout[1..];
+
+    // =========== Render input area (search or add input) ================ //
+    let add_prompt = if app.adding_parent.is_some() {
+        "New subtask: "
+    } else {
+        "New todo: "
+    };
+    // the prompt in front of the text being typed, if anything is
+    let input_prefix = match app.input_mode {
+        InputMode::Searching => {
+            let case_flag = if app.case_sensitive { "Aa" } else { "aa" };
+            let label = match app.search_mode {
+                SearchMode::Substring if app.whole_word => "Search [word]",
+                SearchMode::Substring => "Search",
+                SearchMode::Fuzzy => "Fuzzy search",
+            };
+            Some(format!("{} [{}]: ", label, case_flag))
+        }
+        InputMode::Adding => Some(add_prompt.to_string()),
+        InputMode::Editing => Some("Edit todo: ".to_string()),
+        InputMode::Command => Some(":".to_string()),
+        _ => None,
+    };
+    let input_text = match (&input_prefix, &app.input_mode) {
+        (Some(prefix), InputMode::Searching) => format!("{}{}", prefix, app.search_input),
+        (Some(prefix), _) => format!("{}{}", prefix, app.input_buffer),
+        _ if !app.search_input.is_empty() => format!(
+            "Press '/' to search, ctrl+l to clear (Filter: {})",
+            app.search_input
+        ),
+        _ => "Press '/' to search".to_string(),
+    };
+
+    let input_block_title = match app.input_mode {
+        InputMode::Adding if app.adding_parent.is_some() => "Add subtask",
+        InputMode::Adding => "Add todo",
+        InputMode::Editing => "Edit todo",
+        InputMode::Command => "Command",
+        _ => "Search",
+    };
+
+    // e.g. "Add todo (12/80)", red once the text is over the max
+    let input_block_title = match app.input_mode {
+        InputMode::Adding | InputMode::Editing => {
+            let length = app.input_buffer.chars().count();
+            let (count, style) = match app.max_len {
+                Some(max) if length > max => (format!("{}/{}", length, max), theme.fg(theme.error)),
+                Some(max) => (format!("{}/{}", length, max), Style::default()),
+                None => (length.to_string(), Style::default()),
+            };
+            Line::from(vec![
+                Span::raw(format!("{} (", input_block_title)),
+                Span::styled(count, style),
+                Span::raw(")"),
+            ])
+        }
+        _ => Line::from(input_block_title),
+    };
+
+    let input_area = Paragraph::new(Line::from(input_text))
+        .style(Style::default())
+        .block(
+            Block::default()
+                .title(input_block_title)
+                .borders(Borders::ALL)
+                .border_style(border_style),
+        );
+
+    frame.render_widget(input_area, main_layout[0]);
+
+    // show the terminal cursor where the next char will be typed
+    if let Some(prefix) = input_prefix {
+        // +1 for the block border
+        let text = match app.input_mode {
+            InputMode::Searching => &app.search_input,
+            _ => &app.input_buffer,
+        };
+        let x = main_layout[0].x + 1 + (prefix.width() + width_before(text, app.cursor_pos)) as u16;
+        frame.set_cursor_position((x, main_layout[0].y + 1));
+    }
+
+    // the preview takes the right side of the list area when it's open. on a narrow
+    // terminal it's left out, except while typing notes into it
+    let editing_notes = matches!(app.input_mode, InputMode::EditingNotes);
+    let wide_enough = main_layout[1].width >= MIN_PREVIEW_SPLIT_WIDTH;
+    let (list_area, notes_area) = if (app.show_preview && wide_enough) || editing_notes {
+        let preview_width = app.preview_width.unwrap_or(PREVIEW_WIDTH).clamp(10, 90);
+        let [list_area, notes_area] = Layout::horizontal([
+            Constraint::Percentage(100 - preview_width),
+            Constraint::Percentage(preview_width),
+        ])
+        .areas(main_layout[1]);
+        (list_area, Some(notes_area))
+    } else {
+        (main_layout[1], None)
+    };
+
+    // render the todo list, ListState takes care of the selection highlight and
+    // of scrolling so the selected todo stays visible (wrapped todos included)
+    let number_width = app.filtered_todos.len().to_string().len();
+    let todos: Vec<ListItem> = app
+        .shown_todos()
+        .into_iter()
+        .enumerate()
+        .map(|(i, todo)| {
+            let style = if todo.done {
+                theme
+                    .fg(theme.completed)
+                    .add_modifier(Modifier::DIM | Modifier::CROSSED_OUT)
+            } else {
+                theme.fg(theme.priority(todo.priority))
+            };
+            let symbols = &app.symbols;
+            let pointer = if app.selected_index == Some(i) {
+                symbols.selected.as_str()
+            } else {
+                symbols.unselected.as_str()
+            };
+            let mark = if app.marked.contains(&todo.id) {
+                symbols.marked.as_str()
+            } else {
+                symbols.unmarked.as_str()
+            };
+            // subtasks are indented, todos with subtasks get an arrow to show
+            // if they're collapsed
+            let depth = app.row_depths.get(i).copied().unwrap_or(0);
+            let indent = "  ".repeat(depth);
+            let fold = match (todo.children.is_empty(), todo.collapsed) {
+                (true, _) => "",
+                (false, true) => symbols.collapsed.as_str(),
+                (false, false) => symbols.expanded.as_str(),
+            };
+            let checkbox = if todo.done {
+                symbols.done.as_str()
+            } else {
+                symbols.not_done.as_str()
+            };
+            // room left for the text: borders, the selection symbol, the line number,
+            // the mark, the indent, the arrow and the checkbox. measured in columns so
+            // wide symbols don't push the text past the border
+            let prefix_width = symbols::width(pointer)
+                + number_width
+                + 1
+                + symbols::width(mark)
+                + indent.len()
+                + symbols::width(fold)
+                + symbols::width(checkbox);
+            let text_width = (list_area.width as usize).saturating_sub(2 + prefix_width);
+            let mut spans = Vec::new();
+            // show why each result matched the search
+            let matches = match app.search_mode {
+                SearchMode::Substring => match_ranges(
+                    &todo.text,
+                    &app.search_input,
+                    app.case_sensitive,
+                    app.whole_word,
+                ),
+                SearchMode::Fuzzy => fuzzy_match(&todo.text, &app.search_input, app.case_sensitive)
+                    .map(|(_, indices)| indices.into_iter().map(|i| i..i + 1).collect())
+                    .unwrap_or_default(),
+            };
+            let match_style = theme.match_style();
+            spans.extend(highlight_spans(&todo.text, &matches, match_style));
+            for tag in &todo.tags {
+                spans.push(Span::styled(format!(" #{}", tag), theme.fg(theme.tag)));
+            }
+            if let Some((done, total)) = todo.progress() {
+                spans.push(Span::raw(format!(
+                    " {} {}/{}",
+                    progress_bar(done, total, 10, symbols),
+                    done,
+                    total
+                )));
+            }
+            let mut lines = if app.truncate_todos {
+                vec![truncate_spans(&spans, text_width, &symbols.ellipsis)]
+            } else {
+                wrap_spans(&spans, text_width)
+            };
+            // continuation lines are indented to line up with the text
+            for (line_index, line) in lines.iter_mut().enumerate() {
+                let prefix = if line_index == 0 {
+                    vec![
+                        Span::raw(pointer),
+                        Span::styled(
+                            format!("{:>width$} ", i + 1, width = number_width),
+                            Style::default().add_modifier(Modifier::DIM),
+                        ),
+                        Span::raw(mark),
+                        Span::raw(indent.clone()),
+                        Span::raw(fold),
+                        Span::raw(checkbox),
+                    ]
+                } else {
+                    vec![Span::raw(" ".repeat(prefix_width))]
+                };
+                line.spans.splice(0..0, prefix);
+            }
+            ListItem::new(lines).style(style)
+        })
+        .collect();
+
+    // date headers are rows of their own but not todos, so the app never selects them.
+    // each one is counted as part of the todo below it: its height, and where the
+    // list starts when that todo is scrolled to the top
+    let mut items = Vec::new();
+    let mut item_starts = Vec::new();
+    // the first screen row of each item, headers included, for the scrollbar
+    let mut item_rows = Vec::new();
+    let mut rows = 0;
+    drawn.item_heights.clear();
+    for (i, todo) in todos.into_iter().enumerate() {
+        item_starts.push(items.len());
+        let mut height = todo.height();
+        if let Some(header) = app.group_header(i) {
+            item_rows.push(rows);
+            rows += 1;
+            items.push(ListItem::new(Line::from(vec![
+                Span::raw(" ".repeat(symbols::width(&app.symbols.selected))),
+                Span::styled(header, theme.fg(theme.border).add_modifier(Modifier::BOLD)),
+            ])));
+            height += 1;
+        }
+        item_rows.push(rows);
+        rows += todo.height();
+        drawn.item_heights.push(height);
+        items.push(todo);
+    }
+
+    let mut filters = Vec::new();
+    if app.status_filter != StatusFilter::All {
+        filters.push(app.status_filter.label());
+    }
+    if app.age_filter != AgeFilter::All {
+        filters.push(app.age_filter.label());
+    }
+    let shown = format!("{} shown", app.filtered_todos.len());
+    filters.push(&shown);
+
+    let todos_list = List::new(items)
+        .block(
+            Block::default()
+                .title(format!("{} ({})", app.view.title(), filters.join(", ")))
+                .borders(Borders::ALL)
+                .border_style(border_style),
+        )
+        .style(Style::default())
+        // the pointer is part of each row, so the other todos can have a bullet
+        .highlight_style(theme.fg(theme.selection));
+
+    let selected = app.selected_index.and_then(|i| {
+        let header = usize::from(app.group_header(i).is_some());
+        Some(item_starts.get(i)? + header)
+    });
+    let offset = item_starts
+        .get(app.list_state.offset())
+        .copied()
+        .unwrap_or_default();
+    let mut list_state = app
+        .list_state
+        .clone()
+        .with_selected(selected)
+        .with_offset(offset);
+    frame.render_stateful_widget(todos_list, list_area, &mut list_state);
+    drawn.list_offset = item_starts
+        .iter()
+        .rposition(|start| *start <= list_state.offset())
+        .unwrap_or_default();
+
+    // scrollbar on the right border, only when the list doesn't fit. the area is
+    // read every frame so a resized terminal recomputes the thumb
+    let visible_rows = list_area.height.saturating_sub(2) as usize;
+    drawn.list_area = list_area;
+    if rows > visible_rows {
+        // counted in rows, not todos, so wrapped todos size the thumb right. it can
+        // scroll until the last row is at the bottom
+        let first_row = item_rows.get(list_state.offset()).copied().unwrap_or(0);
+        let mut scrollbar_state = ScrollbarState::new(rows - visible_rows + 1)
+            .viewport_content_length(visible_rows)
+            .position(first_row);
+        frame.render_stateful_widget(
+            Scrollbar::new(ScrollbarOrientation::VerticalRight),
+            list_area.inner(Margin {
+                vertical: 1,
+                horizontal: 0,
+            }),
+            &mut scrollbar_state,
+        );
+    }
+
+    if let Some(notes_area) = notes_area {
+        let selected = app.selected_todo();
+        let placeholder = Style::default().add_modifier(Modifier::DIM);
+        let notes_block = Block::default()
+            .title(if editing_notes {
+                "Notes (ctrl+s: save)"
+            } else {
+                "Preview"
+            })
+            .borders(Borders::ALL)
+            .border_style(border_style);
+        let notes_inner = notes_block.inner(notes_area);
+        let notes = if editing_notes {
+            // not wrapped, so the cursor can be placed from the line and column
+            let before_cursor: String = app.input_buffer.chars().take(app.cursor_pos).collect();
+            let line = before_cursor.matches('\n').count() as u16;
+            let column = before_cursor.rsplit('\n').next().unwrap_or("").width() as u16;
+            let scroll = line.saturating_sub(notes_inner.height.saturating_sub(1));
+            frame.set_cursor_position((
+                (notes_inner.x + column).min(notes_inner.right().saturating_sub(1)),
+                notes_inner.y + line - scroll,
+            ));
+            Paragraph::new(app.input_buffer.as_str()).scroll((scroll, 0))
+        } else {
+            match selected {
+                None => Paragraph::new("No todo selected").style(placeholder),
+                Some(todo) => Paragraph::new(preview_lines(todo, placeholder)),
+            }
+            .wrap(Wrap { trim: false })
+        };
+        frame.render_widget(notes_block, notes_area);
+        frame.render_widget(notes, notes_inner);
+    }
+
+    // share of every todo in the list that's done, searches and filters don't change it
+    let (done, total) = app.completion();
+    let ratio = if total == 0 {
+        0.0
+    } else {
+        done as f64 / total as f64
+    };
+    let gauge = Gauge::default()
+        .ratio(ratio)
+        .label(format!("{}% done", (ratio * 100.0).round()))
+        .gauge_style(theme.fg(theme.info));
+    frame.render_widget(gauge, main_layout[2]);
+
+    // update status bar to show search instructions. only the main keys fit on one
+    // line, the rest are in the ? help
+    let keymap = &app.keymap;
+    let normal_mode_text = format!(
+        "Normal Mode | {}: add, {}: edit, {}: remove, space: done, {}: search, {}: quit, ?: help",
+        keymap.label(Action::Add),
+        keymap.label(Action::Edit),
+        keymap.label(Action::Delete),
+        keymap.label(Action::Search),
+        keymap.label(Action::Quit),
+    );
+    let mode_text = match app.input_mode {
+        InputMode::Normal if app.view == View::Archived => {
+            "Archive | r: restore, V: trash, ?: help"
+        }
+        InputMode::Normal if app.view == View::Trash => {
+            "Trash | r: restore, E: empty trash, V: back to todos, ?: help"
+        }
+        InputMode::Normal => normal_mode_text.as_str(),
+        InputMode::Searching => "Search Mode | Enter/Esc: keep filter, ctrl+l: clear filter, ctrl+s: toggle case, ctrl+w: whole word, ctrl+f: fuzzy",
+        InputMode::Adding => "Add Mode | Enter: save todo, ctrl+v: paste, ctrl+w/ctrl+u: delete word/all, Esc: cancel",
+        InputMode::Confirming => "Delete? | y/Enter: continue, n/Esc: cancel",
+        InputMode::Editing => "Edit Mode | Enter: save changes, ctrl+v: paste, ctrl+w/ctrl+u: delete word/all, Esc: cancel",
+        InputMode::DiscardConfirm => "Discard changes? | y: discard, n/Esc: keep editing",
+        InputMode::QuitConfirm => "Quit? | s/y: save and quit, d/n: quit without saving, Esc/c: cancel",
+        InputMode::EmptyTrashConfirm => "Empty trash? | y: continue, n/Esc: cancel",
+        InputMode::ClearAllConfirm => "Clear all? | y: continue, n/Esc: cancel",
+        InputMode::Command => "Command | add <text>, delete, sort <mode>, import merge <path>, w, q, wq, Enter: run, Esc: cancel",
+        InputMode::EditingNotes => "Notes | Enter: new line, ctrl+s: save, ctrl+v: paste, Esc: cancel",
+        InputMode::Details => "Details | any key: close",
+        InputMode::Help => "Help | j/k: scroll, any other key: close",
+    };
+    let status_block = Block::default()
+        .title(format!("Status (sort: {})", app.sort_mode.label()))
+        .borders(Borders::ALL)
+        .border_style(border_style);
+    let status_inner = status_block.inner(main_layout[3]);
+    frame.render_widget(status_block, main_layout[3]);
+
+    // clock and counts on the right, dropped when the hints would get too squeezed
+    let mut summary = format!("{} | {}", app.summary(), Local::now().format("%H:%M"));
+    // the list title says so too, but it's easy to miss that done todos are hidden
+    if app.status_filter != StatusFilter::All {
+        summary = format!("{} only | {}", app.status_filter.label(), summary);
+    }
+    let summary_width = summary.width() as u16;
+    let [hints_area, summary_area] = if status_inner.width > summary_width * 2 {
+        Layout::horizontal([Constraint::Min(0), Constraint::Length(summary_width)])
+            .spacing(1)
+            .areas(status_inner)
+    } else {
+        [status_inner, Rect::default()]
+    };
+    let (status_text, status_style) = match &app.message {
+        Some(message) if message.severity == Severity::Error => {
+            (message.text.as_str(), theme.fg(theme.error))
+        }
+        Some(message) => (message.text.as_str(), theme.fg(theme.info)),
+        None => (mode_text, Style::default()),
+    };
+    frame.render_widget(
+        Paragraph::new(Line::styled(
+            truncate(
+                status_text,
+                hints_area.width as usize,
+                &app.symbols.ellipsis,
+            ),
+            status_style,
+        )),
+        hints_area,
+    );
+    frame.render_widget(Paragraph::new(summary), summary_area);
+
+    // render configuration dialog if needed
+    if app.show_confirmation {
+        // create a temporal string that lives long enough to be used in the Line::from function
+        let fallback_string = String::new();
+        let selected_todo = app
+            .selected_todo()
+            .map(|todo| &todo.text)
+            .unwrap_or(&fallback_string);
+
+        // with marked todos the delete applies to those instead of the selection
+        let (question, subject) = if app.marked.is_empty() {
+            ("Delete this todo?".to_string(), selected_todo.as_str())
+        } else {
+            (format!("Delete {} marked todos?", app.marked.len()), "")
+        };
+
+        let popup_area = centered_rect(60, 30, frame.area());
+        let confirmation = Paragraph::new(vec![
+            Line::from(question),
+            Line::from(""),
+            Line::from(subject),
+            Line::from(""),
+            // yes is the default, Enter picks it
+            Line::from(vec![
+                Span::styled(
+                    " Yes (y/Enter) ",
+                    theme
+                        .fg(theme.selection)
+                        .add_modifier(Modifier::REVERSED | Modifier::BOLD),
+                ),
+                Span::raw("   "),
+                Span::raw(" No (n/Esc) "),
+            ]),
+        ])
+        .alignment(ratatui::layout::Alignment::Center)
+        .block(
+            Block::default()
+                .title("Confirm delete")
+                .borders(Borders::ALL)
+                .border_style(border_style),
+        );
+
+        draw_backdrop(frame, theme, border_style, popup_area);
+        frame.render_widget(confirmation, popup_area);
+    }
+
+    if matches!(app.input_mode, InputMode::Details) {
+        if let Some(todo) = app.selected_todo() {
+            let popup_area = centered_rect(60, 30, frame.area());
+            let details = Paragraph::new(vec![
+                Line::from(todo.text.as_str()),
+                Line::from(""),
+                Line::from(format!(
+                    "Created: {}",
+                    todo.created_at.format("%Y-%m-%d %H:%M")
+                )),
+                Line::from(format!(
+                    "Updated: {}",
+                    todo.updated_at.format("%Y-%m-%d %H:%M")
+                )),
+            ])
+            .alignment(ratatui::layout::Alignment::Center)
+            .block(
+                Block::default()
+                    .title("Details")
+                    .borders(Borders::ALL)
+                    .border_style(border_style),
+            );
+
+            draw_backdrop(frame, theme, border_style, popup_area);
+            frame.render_widget(details, popup_area);
+        }
+    }
+
+    if matches!(app.input_mode, InputMode::Help) {
+        let popup_area = centered_rect(60, 80, frame.area());
+        let lines = help_lines(&app.keymap);
+        // don't scroll past the last line
+        let visible = popup_area.height.saturating_sub(2);
+        drawn.help_scroll = app
+            .help_scroll
+            .min((lines.len() as u16).saturating_sub(visible));
+        let help = Paragraph::new(lines).scroll((drawn.help_scroll, 0)).block(
+            Block::default()
+                .title("Help")
+                .borders(Borders::ALL)
+                .border_style(border_style),
+        );
+
+        draw_backdrop(frame, theme, border_style, popup_area);
+        frame.render_widget(help, popup_area);
+    }
+
+    if matches!(app.input_mode, InputMode::ClearAllConfirm) {
+        let popup_area = centered_rect(60, 30, frame.area());
+        let confirmation = Paragraph::new(vec![
+            Line::from(format!("Delete all {} todos?", app.todos.len())),
+            Line::from(""),
+            Line::from("They go to the trash and 'u' brings them back"),
+            Line::from(""),
+            Line::from("Press 'y' to confirm or 'n'/Esc to cancel"),
+        ])
+        .alignment(ratatui::layout::Alignment::Center)
+        .block(
+            Block::default()
+                .title("Clear all")
+                .borders(Borders::ALL)
+                .border_style(border_style),
+        );
+
+        draw_backdrop(frame, theme, border_style, popup_area);
+        frame.render_widget(confirmation, popup_area);
+    }
+
+    if matches!(app.input_mode, InputMode::EmptyTrashConfirm) {
+        let popup_area = centered_rect(60, 30, frame.area());
+        let confirmation = Paragraph::new(vec![
+            Line::from(format!(
+                "Permanently delete the {} todos in the trash?",
+                app.trash.len()
+            )),
+            Line::from(""),
+            Line::from("Press 'y' to confirm or 'n'/Esc to cancel"),
+        ])
+        .alignment(ratatui::layout::Alignment::Center)
+        .block(
+            Block::default()
+                .title("Empty trash")
+                .borders(Borders::ALL)
+                .border_style(border_style),
+        );
+
+        draw_backdrop(frame, theme, border_style, popup_area);
+        frame.render_widget(confirmation, popup_area);
+    }
+
+    if matches!(app.input_mode, InputMode::DiscardConfirm) {
+        let popup_area = centered_rect(50, 20, frame.area());
+        let confirmation = Paragraph::new(vec![Line::from("Discard changes? (y/n)")])
+            .alignment(ratatui::layout::Alignment::Center)
+            .block(
+                Block::default()
+                    .title("Discard")
+                    .borders(Borders::ALL)
+                    .border_style(border_style),
+            );
+
+        draw_backdrop(frame, theme, border_style, popup_area);
+        frame.render_widget(confirmation, popup_area);
+    }
+
+    if matches!(app.input_mode, InputMode::QuitConfirm) {
+        let popup_area = centered_rect(60, 30, frame.area());
+        let confirmation = Paragraph::new(vec![
+            Line::from("Your changes couldn't be saved."),
+            Line::from(""),
+            Line::from("s: save and quit, d: discard them and quit, Esc: cancel"),
+        ])
+        .alignment(ratatui::layout::Alignment::Center)
+        .block(
+            Block::default()
+                .title("Quit")
+                .borders(Borders::ALL)
+                .border_style(border_style),
+        );
+
+        draw_backdrop(frame, theme, border_style, popup_area);
+        frame.render_widget(confirmation, popup_area);
+    }
+
+    drawn
+}
+
+// dims the list behind a popup and clears the popup's own area so nothing from the
+// list shows through its padding
+fn draw_backdrop(frame: &mut Frame, theme: Theme, border_style: Style, popup_area: Rect) {
+    frame.render_widget(
+        Block::default()
+            .style(theme.bg(theme.popup_backdrop).add_modifier(Modifier::DIM))
+            .borders(Borders::ALL)
+            .border_style(border_style),
+        frame.area(),
+    );
+    frame.render_widget(Clear, popup_area);
+}
+
+// the help popup, rebindable keys are read from the keymap so the list matches keys.toml
+fn help_lines(keymap: &Keymap) -> Vec<Line<'static>> {
+    let normal_keys = action::normal_mode_help(keymap);
+    let groups = [
+        ("Normal mode", normal_keys),
+        (
+            "Search mode",
+            vec![
+                ("enter/Esc".to_string(), "stop typing, keeping the filter"),
+                ("left/right/home/end".to_string(), "move the cursor"),
+                (
+                    "ctrl+l".to_string(),
+                    "clear the filter (in normal mode too)",
+                ),
+                ("ctrl+s".to_string(), "toggle case sensitivity"),
+                ("ctrl+f".to_string(), "toggle fuzzy search"),
+                ("ctrl+w".to_string(), "toggle whole word search"),
+            ],
+        ),
+        (
+            "Commands",
+            vec![
+                (":add <text>".to_string(), "add a todo"),
+                (":delete".to_string(), "delete the selected todo"),
+                (
+                    ":sort <mode>".to_string(),
+                    "alphabetical, created, priority, completed or manual",
+                ),
+                (":list <name>".to_string(), "switch to a list, creating it"),
+                (":rename <name>".to_string(), "rename the current list"),
+                (":dellist".to_string(), "delete the current list if empty"),
+                (
+                    ":import merge <path>".to_string(),
+                    "add the todos of another file",
+                ),
+                (":w".to_string(), "save"),
+                (":q".to_string(), "quit (:q! without asking)"),
+                (":wq".to_string(), "save and quit"),
+            ],
+        ),
+        (
+            "Add/edit mode",
+            vec![
+                ("enter".to_string(), "save"),
+                ("Esc".to_string(), "cancel"),
+                ("left/right/home/end".to_string(), "move the cursor"),
+                ("ctrl+v".to_string(), "paste, one todo per line when adding"),
+                ("ctrl+w".to_string(), "delete the word before the cursor"),
+                ("ctrl+u".to_string(), "clear the input"),
+            ],
+        ),
+        (
+            "Notes",
+            vec![
+                ("enter".to_string(), "new line"),
+                ("ctrl+s".to_string(), "save the notes"),
+                ("Esc".to_string(), "cancel"),
+            ],
+        ),
+    ];
+
+    let mut lines = Vec::new();
+    for (title, keys) in groups {
+        if !lines.is_empty() {
+            lines.push(Line::from(""));
+        }
+        lines.push(Line::styled(
+            title,
+            Style::default().add_modifier(Modifier::BOLD),
+        ));
+        for (key, description) in keys {
+            lines.push(Line::from(format!("  {:<20}{}", key, description)));
+        }
+    }
+    lines
+}
+
+// splits `text` into spans, giving the `highlighted` char ranges `highlight_style`
+fn highlight_spans<'a>(
+    text: &'a str,
+    highlighted: &[Range<usize>],
+    highlight_style: Style,
+) -> Vec<Span<'a>> {
+    let byte_index = |char_index: usize| {
+        text.char_indices()
+            .nth(char_index)
+            .map(|(i, _)| i)
+            .unwrap_or(text.len())
+    };
+
+    let mut spans = Vec::new();
+    let mut last = 0;
+    for range in highlighted {
+        let (start, end) = (byte_index(range.start), byte_index(range.end));
+        if start > last {
+            spans.push(Span::raw(&text[last..start]));
+        }
+        spans.push(Span::styled(&text[start..end], highlight_style));
+        last = end;
+    }
+    if last < text.len() {
+        spans.push(Span::raw(&text[last..]));
+    }
+    spans
+}
+
+// how many columns the first `cursor` chars of `text` take up on screen, wide chars
+// like CJK and most emoji take two
+fn width_before(text: &str, cursor: usize) -> usize {
+    text.chars()
+        .take(cursor)
+        .map(|c| c.width().unwrap_or(0))
+        .sum()
+}
+
+// the longest start of `text` that fits in `width` columns
+fn fit_width(text: &str, width: usize) -> &str {
+    let mut used = 0;
+    for (i, c) in text.char_indices() {
+        used += c.width().unwrap_or(0);
+        if used > width {
+            return &text[..i];
+        }
+    }
+    text
+}
+
+// greedy word wrap of `spans` into lines at most `width` columns wide, keeping each
+// span's style. words that don't fit on a line of their own get split
+fn wrap_spans(spans: &[Span], width: usize) -> Vec<Line<'static>> {
+    let width = width.max(1);
+    let mut lines = Vec::new();
+    let mut line = Vec::new();
+    let mut line_width = 0;
+    for span in spans {
+        for word in span.content.split_inclusive(' ') {
+            if line_width > 0 && line_width + word.trim_end().width() > width {
+                lines.push(Line::from(mem::take(&mut line)));
+                line_width = 0;
+            }
+            let mut rest = word;
+            while rest.trim_end().width() > width - line_width {
+                let mut split = fit_width(rest, width - line_width).len();
+                // a wide char alone on a line that's one column wide still has to go
+                if split == 0 && line_width == 0 {
+                    split = rest.chars().next().map_or(rest.len(), char::len_utf8);
+                }
+                line.push(Span::styled(rest[..split].to_string(), span.style));
+                lines.push(Line::from(mem::take(&mut line)));
+                line_width = 0;
+                rest = &rest[split..];
+            }
+            line_width += rest.width();
+            line.push(Span::styled(rest.to_string(), span.style));
+        }
+    }
+    if !line.is_empty() || lines.is_empty() {
+        lines.push(Line::from(line));
+    }
+    lines
+}
+
+// `spans` on one line, cut at `width` columns with an ellipsis if they don't fit
+fn truncate_spans(spans: &[Span], width: usize, ellipsis: &str) -> Line<'static> {
+    let total: usize = spans.iter().map(|span| span.content.width()).sum();
+    if total <= width {
+        let spans: Vec<Span> = spans
+            .iter()
+            .map(|span| Span::styled(span.content.to_string(), span.style))
+            .collect();
+        return Line::from(spans);
+    }
+    let mut line = Vec::new();
+    let mut room = width.saturating_sub(symbols::width(ellipsis));
+    for span in spans {
+        let kept = fit_width(&span.content, room);
+        room -= kept.width();
+        line.push(Span::styled(kept.to_string(), span.style));
+    }
+    line.push(Span::raw(ellipsis.to_string()));
+    Line::from(line)
+}
+
+// a gauge like "■■■■■■□□□□", `width` cells long
+fn progress_bar(done: usize, total: usize, width: usize, symbols: &Symbols) -> String {
+    let filled = (done * width).checked_div(total).unwrap_or(0);
+    symbols.bar_filled.repeat(filled) + &symbols.bar_empty.repeat(width - filled)
+}
+
+// everything about a todo for the preview pane, notes last
+fn preview_lines(todo: &Todo, placeholder: Style) -> Vec<Line<'_>> {
+    let mut lines = vec![
+        Line::styled(
+            todo.text.as_str(),
+            Style::default().add_modifier(Modifier::BOLD),
+        ),
+        Line::from(""),
+        Line::from(format!(
+            "Status: {}, {} priority",
+            if todo.done { "done" } else { "not done" },
+            todo.priority.label()
+        )),
+    ];
+    if !todo.tags.is_empty() {
+        let tags: Vec<String> = todo.tags.iter().map(|tag| format!("#{}", tag)).collect();
+        lines.push(Line::from(format!("Tags: {}", tags.join(" "))));
+    }
+    if let Some((done, total)) = todo.progress() {
+        lines.push(Line::from(format!("Subtasks: {}/{} done", done, total)));
+    }
+    lines.push(Line::from(format!(
+        "Created: {}",
+        todo.created_at.format("%Y-%m-%d %H:%M")
+    )));
+    lines.push(Line::from(format!(
+        "Updated: {}",
+        todo.updated_at.format("%Y-%m-%d %H:%M")
+    )));
+    lines.push(Line::from(""));
+    if todo.notes.is_empty() {
+        lines.push(Line::styled(
+            "No notes, press 'e' to write some",
+            placeholder,
+        ));
+    } else {
+        lines.extend(todo.notes.lines().map(Line::from));
+    }
+    lines
+}
+
+// cuts `text` down to `width` columns, ending in `ellipsis` when something was cut off
+fn truncate(text: &str, width: usize, ellipsis: &str) -> String {
+    if text.width() <= width {
+        return text.to_string();
+    }
+    let room = width.saturating_sub(symbols::width(ellipsis));
+    let mut truncated = fit_width(text, room).to_string();
+    if width >= symbols::width(ellipsis) {
+        truncated.push_str(ellipsis);
+    }
+    truncated
+}
+
+// helper function to create a centered rect using percentage of the available area.
+// never shorter than 5 rows (a border, three lines of text and a border) if `r` has them
+fn centered_rect(percent_x: u16, percent_y: u16, r: Rect) -> Rect {
+    let percent_y = percent_y.max((500 / r.height.max(1)).min(100));
+    let popup_layout = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints([
+            Constraint::Percentage((100 - percent_y) / 2),
+            Constraint::Percentage(percent_y),
+            Constraint::Percentage((100 - percent_y) / 2),
+        ])
+        .split(r);
+
+    // then cut the middle vertical piece into three horizontal pieces
+    Layout::default()
+        .direction(Direction::Horizontal)
+        .constraints([
+            Constraint::Percentage((100 - percent_x) / 2),
+            Constraint::Percentage(percent_x),
+            Constraint::Percentage((100 - percent_x) / 2),
+        ])
+        .split(popup_layout[1])[1] // return the middle chunk
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crossterm::event::{KeyCode, KeyModifiers};
+    use ratatui::{backend::TestBackend, Terminal};
+
+    // the frame as plain text, one line per row
+    fn render(app: &App, width: u16, height: u16) -> String {
+        let mut terminal = Terminal::new(TestBackend::new(width, height)).unwrap();
+        terminal
+            .draw(|frame| {
+                ui(frame, app);
+            })
+            .unwrap();
+        let buffer = terminal.backend().buffer();
+        (0..height)
+            .map(|y| {
+                (0..width)
+                    .map(|x| buffer[(x, y)].symbol())
+                    .collect::<String>()
+            })
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+
+    fn app_with(texts: &[&str]) -> App {
+        App::from_lines(texts.iter().map(|text| text.to_string()))
+    }
+
+    #[test]
+    fn the_list_shows_every_todo() {
+        let app = app_with(&["buy milk", "write report"]);
+        let screen = render(&app, 80, 20);
+        assert!(screen.contains("Todos (2 shown)"));
+        assert!(screen.contains("-> 1   [ ] buy milk"));
+        assert!(screen.contains("   2   [ ] write report"));
+        assert!(screen.contains("Normal Mode"));
+    }
+
+    #[test]
+    fn the_other_todos_get_the_unselected_bullet() {
+        let mut app = app_with(&["buy milk", "write report"]);
+        let config: symbols::SymbolsConfig = toml::from_str("unselected = \"- \"").unwrap();
+        app.symbols = Symbols::from_config(&config, true);
+        let screen = render(&app, 80, 20);
+        assert!(screen.contains("-> 1   [ ] buy milk"));
+        assert!(screen.contains("-  2   [ ] write report"));
+    }
+
+    #[test]
+    fn the_list_scrolls_to_keep_the_selection_visible() {
+        let texts: Vec<String> = (1..=30).map(|i| format!("todo {}", i)).collect();
+        let mut app = App::from_lines(texts.into_iter());
+        app.select_last();
+        let screen = render(&app, 80, 20);
+        assert!(screen.contains("-> 30   [ ] todo 30"));
+        assert!(!screen.contains("todo 1 "));
+        // the scrollbar thumb sits at the bottom of the right border
+        assert!(screen.contains('█'));
+    }
+
+    #[test]
+    fn date_groups_get_a_header_row() {
+        let mut app = app_with(&["buy milk", "write report"]);
+        app.toggle_group_by_date();
+        let screen = render(&app, 80, 20);
+        assert!(screen.contains("│   Today "));
+        assert!(screen.contains("-> 1   [ ] buy milk"));
+    }
+
+    #[test]
+    fn the_status_bar_follows_the_mode() {
+        let modes = [
+            (InputMode::Normal, "q/Esc: quit, ?: help"),
+            (InputMode::Searching, "Search Mode"),
+            (InputMode::Adding, "Add Mode"),
+            (InputMode::Editing, "Edit Mode"),
+            (InputMode::EditingNotes, "Notes |"),
+            (InputMode::Command, "Command |"),
+            (InputMode::ClearAllConfirm, "Clear all?"),
+            (InputMode::DiscardConfirm, "Discard changes?"),
+            (InputMode::Details, "Details |"),
+            (InputMode::Help, "Help |"),
+        ];
+        for (mode, hint) in modes {
+            let mut app = app_with(&["one"]);
+            app.input_mode = mode;
+            let screen = render(&app, 200, 30);
+            assert!(screen.contains(hint), "{} missing from:\n{}", hint, screen);
+        }
+    }
+
+    #[test]
+    fn the_delete_popup_shows_the_selected_todo() {
+        let mut app = app_with(&["one", "two"]);
+        app.move_selection_down();
+        app.start_delete_confirmation();
+        let screen = render(&app, 80, 24);
+        assert!(screen.contains("Delete this todo?"));
+        assert!(screen.contains("two"));
+        assert!(screen.contains("Yes (y/Enter)"));
+    }
+
+    #[test]
+    fn the_list_doesnt_show_through_the_discard_popup() {
+        let texts: Vec<String> = (1..=30).map(|i| format!("todo number {}", i)).collect();
+        let mut app = App::from_lines(texts.into_iter());
+        app.input_mode = InputMode::DiscardConfirm;
+        let screen = render(&app, 80, 24);
+        // the popup's row is blank around the question, not "mber 7"
+        assert!(
+            screen.contains("│        Discard changes? (y/n)        │"),
+            "{}",
+            screen
+        );
+    }
+
+    #[test]
+    fn tiny_terminals_get_a_message_instead() {
+        let app = app_with(&["one"]);
+        let screen = render(&app, 15, 5);
+        assert!(screen.contains("Terminal too"));
+        assert!(!screen.contains("one"));
+    }
+
+    #[test]
+    fn wide_chars_take_two_columns() {
+        let mut app = app_with(&[]);
+        app.input_mode = InputMode::Adding;
+        for c in "买菜 🛒".chars() {
+            app.insert_char(c);
+        }
+        assert_eq!(app.cursor_pos, 4);
+        assert_eq!(width_before(&app.input_buffer, app.cursor_pos), 7);
+        app.move_cursor_left();
+        assert_eq!(width_before(&app.input_buffer, app.cursor_pos), 5);
+
+        // the terminal cursor lands right after the cart, past the border and the
+        // "New todo: " prompt
+        app.move_cursor_to_end();
+        let mut terminal = Terminal::new(TestBackend::new(40, 12)).unwrap();
+        terminal
+            .draw(|frame| {
+                ui(frame, &app);
+            })
+            .unwrap();
+        assert_eq!(
+            terminal.get_cursor_position().unwrap(),
+            (1 + 10 + 7, 2).into()
+        );
+
+        // and cutting a line never splits a wide char
+        assert_eq!(truncate("买菜买菜", 6, "…"), "买菜…");
+        assert_eq!(truncate("买菜买菜", 5, "…"), "买菜…");
+        assert_eq!(truncate("buy milk", 6, "..."), "buy...");
+    }
+
+    #[test]
+    fn long_todos_wrap_under_their_text_and_highlight_every_line() {
+        let app = app_with(&["alpha beta gamma delta epsilon zeta eta theta"]);
+        let mut terminal = Terminal::new(TestBackend::new(40, 20)).unwrap();
+        terminal
+            .draw(|frame| {
+                ui(frame, &app);
+            })
+            .unwrap();
+        let buffer = terminal.backend().buffer();
+        let cell = |x: u16, y: u16| buffer[(x, y)].symbol();
+        let (x, y) = (0..20)
+            .flat_map(|y| (0..40).map(move |x| (x, y)))
+            .find(|&(x, y)| cell(x, y) == "a" && cell(x + 1, y) == "l")
+            .unwrap();
+        // the continuation starts in the same column as the text above it
+        assert_ne!(cell(x, y + 1), " ");
+        assert!((1..x).all(|x| cell(x, y + 1) == " "));
+        let selection = app.theme.fg(app.theme.selection).fg.unwrap();
+        assert_eq!(buffer[(x, y + 1)].fg, selection);
+    }
+
+    #[test]
+    fn the_scrollbar_counts_wrapped_rows() {
+        // three todos of seven rows each, with room for ten
+        let long = "alpha beta gamma delta epsilon zeta eta theta iota kappa lambda mu";
+        let app = app_with(&[long, long, long]);
+        let screen = render(&app, 24, 20);
+        let thumb: Vec<usize> = screen
+            .lines()
+            .enumerate()
+            .filter(|(_, line)| line.ends_with('█'))
+            .map(|(row, _)| row)
+            .collect();
+        // right under the arrow, and 10 of the 21 rows long on the 8 cell track
+        assert_eq!(thumb.first(), Some(&6), "{}", screen);
+        assert_eq!(thumb.len(), 4, "{}", screen);
+    }
+
+    #[test]
+    fn the_status_bar_shows_the_status_filter() {
+        let mut app = app_with(&["one"]);
+        assert!(!render(&app, 100, 20).contains(" only |"));
+        app.cycle_status_filter();
+        assert!(render(&app, 100, 20).contains("Active only | 1 todos, 0 done"));
+    }
+
+    #[test]
+    fn esc_keeps_the_filter_on_screen_and_ctrl_l_clears_it() {
+        let mut app = app_with(&["buy milk", "call mom"]);
+        for c in "/mil".chars() {
+            app.handle_key(KeyCode::Char(c), KeyModifiers::NONE);
+        }
+        app.handle_key(KeyCode::Esc, KeyModifiers::NONE);
+        let screen = render(&app, 100, 20);
+        assert!(screen.contains("Press '/' to search, ctrl+l to clear (Filter: mil)"));
+        assert!(!screen.contains("call mom"));
+
+        app.handle_key(KeyCode::Char('l'), KeyModifiers::CONTROL);
+        let screen = render(&app, 100, 20);
+        assert!(screen.contains("call mom"));
+        assert!(!screen.contains("Filter:"));
+    }
+
+    #[test]
+    fn notes_wrap_in_the_preview_pane() {
+        let mut app = app_with(&["one"]);
+        app.todos[0].notes = "a note long enough that it has to wrap in the pane".to_string();
+        app.filter_todos();
+        app.show_preview = true;
+        let screen = render(&app, 80, 30);
+        assert!(screen.contains("Preview"));
+        assert!(screen.contains("a note long enough"));
+        assert!(screen.contains("wrap in the pane"));
+    }
+
+    #[test]
+    fn subtasks_are_indented_under_a_parent_showing_its_progress() {
+        let mut app = app_with(&["parent", "other"]);
+        for text in ["first step", "second step"] {
+            app.select_first();
+            app.start_adding_subtask();
+            app.input_buffer = text.to_string();
+            app.add_todo();
+        }
+        app.selected_index = Some(1);
+        app.toggle_selected();
+        let screen = render(&app, 80, 20);
+        let row = |text: &str| screen.lines().find(|line| line.contains(text)).unwrap();
+        assert!(row("parent").contains("1/2"), "{}", screen);
+        // counted in cells, the fold arrow is more than one byte
+        let column = |text: &str| {
+            let line = row(text);
+            line[..line.find(text).unwrap()].chars().count()
+        };
+        // past the top level todos, level with the parent's text after its arrow
+        assert!(column("first step") > column("other"), "{}", screen);
+        assert_eq!(column("first step"), column("parent"));
+        assert_eq!(column("first step"), column("second step"));
+        assert!(row("first step").contains("[x]"));
+        assert!(row("second step").contains("[ ]"));
+    }
+
+    #[test]
+    fn the_gauge_counts_every_todo_done() {
+        let app = app_with(&[]);
+        assert!(render(&app, 80, 20).contains("0% done"));
+
+        let mut app = app_with(&["one", "two", "three", "four"]);
+        app.toggle_selected();
+        // a search doesn't change it
+        app.search_input = "two".to_string();
+        app.filter_todos();
+        assert!(render(&app, 80, 20).contains("25% done"));
+
+        app.clear_search();
+        app.select_last();
+        app.toggle_selected();
+        assert!(render(&app, 80, 20).contains("50% done"));
+    }
+}